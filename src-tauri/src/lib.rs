@@ -1,13 +1,16 @@
 use tauri::{Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
 
 #[cfg(target_os = "macos")]
 use tauri::{
     menu::{CheckMenuItem, MenuBuilder, MenuItem, PredefinedMenuItem},
-    tray::TrayIconBuilder,
+    tray::{TrayIcon, TrayIconBuilder},
     Wry,
 };
 
+mod logging;
 mod scheduler;
+mod window;
 
 #[cfg(target_os = "macos")]
 const TRAY_ICON: tauri::image::Image<'_> = tauri::include_image!("icons/32x32.png");
@@ -15,7 +18,50 @@ const TRAY_ICON: tauri::image::Image<'_> = tauri::include_image!("icons/32x32.pn
 #[cfg(target_os = "macos")]
 struct TrayState {
     click_through_item: CheckMenuItem<Wry>,
+    all_workspaces_item: CheckMenuItem<Wry>,
     click_through_enabled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    tray: TrayIcon<Wry>,
+}
+
+/// 托盘图标随宠物状态切换：normal 为默认的单色模板图标，running 显示原色图标，
+/// failing 为红色剪影；未知的 mood 回退到默认
+#[cfg(target_os = "macos")]
+fn tray_icon_for_mood(mood: &str) -> (tauri::image::Image<'static>, bool) {
+    match mood {
+        "running" => (TRAY_ICON, false),
+        "failing" => {
+            let rgba = TRAY_ICON
+                .rgba()
+                .chunks_exact(4)
+                .flat_map(|px| [0xE5, 0x48, 0x4D, px[3]])
+                .collect();
+            (
+                tauri::image::Image::new_owned(rgba, TRAY_ICON.width(), TRAY_ICON.height()),
+                false,
+            )
+        }
+        _ => (TRAY_ICON, true),
+    }
+}
+
+#[cfg(target_os = "macos")]
+#[tauri::command]
+fn set_tray_tooltip(text: String, state: tauri::State<'_, TrayState>) -> Result<(), String> {
+    state
+        .tray
+        .set_tooltip(Some(text))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "macos")]
+#[tauri::command]
+fn set_tray_icon(mood: String, state: tauri::State<'_, TrayState>) -> Result<(), String> {
+    let (icon, as_template) = tray_icon_for_mood(&mood);
+    state.tray.set_icon(Some(icon)).map_err(|e| e.to_string())?;
+    state
+        .tray
+        .set_icon_as_template(as_template)
+        .map_err(|e| e.to_string())
 }
 
 #[cfg(target_os = "macos")]
@@ -33,6 +79,143 @@ fn set_tray_click_through_checked(
         .map_err(|e| e.to_string())
 }
 
+// 与下方 generate_handler! 列表保持一致，供前端按平台做功能探测
+const SHARED_COMMANDS: &[&str] = &[
+    "get_available_commands",
+    "is_shortcut_available",
+    "set_visible_on_all_workspaces",
+    "set_click_through_mode",
+    "set_click_through_regions",
+    "update_click_through_hover",
+    "set_developer_mode",
+    "get_developer_mode",
+    "toggle_devtools",
+    "window_list_monitors",
+    "window_move_to_monitor",
+    "set_window_decorations",
+    "set_window_level",
+    "scheduler_create_task",
+    "scheduler_list_presets",
+    "scheduler_create_from_preset",
+    "scheduler_import_crontab",
+    "scheduler_validate_bundle",
+    "scheduler_normalize_config",
+    "scheduler_get_task",
+    "scheduler_get_tasks_by_ids",
+    "scheduler_describe_task",
+    "scheduler_get_effective_schedule",
+    "scheduler_get_all_tasks",
+    "scheduler_get_countdowns",
+    "scheduler_get_agenda",
+    "scheduler_get_hourly_histogram",
+    "scheduler_export_ics",
+    "scheduler_estimate_daily_load",
+    "scheduler_search_tasks",
+    "scheduler_find_tasks_referencing",
+    "scheduler_reorder_tasks",
+    "scheduler_update_task",
+    "scheduler_preview_update",
+    "scheduler_delete_task",
+    "scheduler_delete_tasks",
+    "scheduler_delete_where",
+    "scheduler_enable_task",
+    "scheduler_disable_after_next_run",
+    "scheduler_debug_loop",
+    "scheduler_toggle_exclusive",
+    "scheduler_execute_now",
+    "scheduler_test_action",
+    "scheduler_replay_execution",
+    "scheduler_set_next_run",
+    "scheduler_snooze_all",
+    "scheduler_confirm_execution",
+    "scheduler_complete_execution",
+    "scheduler_report_idle",
+    "scheduler_report_power_state",
+    "scheduler_report_event",
+    "scheduler_subscribe_task",
+    "scheduler_unsubscribe_task",
+    "scheduler_tick_now",
+    "scheduler_set_tick_interval",
+    "scheduler_get_tick_interval",
+    "scheduler_get_tick_diagnostics",
+    "scheduler_get_runtime_state",
+    "scheduler_debug_compute",
+    "scheduler_simulate_range",
+    "scheduler_get_safe_mode",
+    "scheduler_exit_safe_mode",
+    "scheduler_get_executions",
+    "scheduler_get_execution_result",
+    "scheduler_get_latest_executions",
+    "scheduler_get_reliability",
+    "scheduler_get_dispatch_stats",
+    "scheduler_get_stale_tasks",
+    "scheduler_reassign_executions",
+    "scheduler_clear_executions",
+    "scheduler_find_orphaned_executions",
+    "scheduler_purge_orphaned_executions",
+    "scheduler_get_audit",
+    "scheduler_set_dnd",
+    "scheduler_get_dnd",
+    "scheduler_set_log_level",
+    "scheduler_get_log_path",
+    "scheduler_set_mock_time",
+    "scheduler_get_rate_limits",
+    "scheduler_set_rate_limits",
+    "scheduler_get_notification_rate_limit",
+    "scheduler_set_notification_rate_limit",
+    "scheduler_get_config",
+    "scheduler_set_config",
+    "scheduler_get_disabled_action_types",
+    "scheduler_set_disabled_action_types",
+    "scheduler_set_max_enabled_tasks",
+    "scheduler_check_integrity",
+    "scheduler_self_test",
+    "scheduler_reload",
+    "scheduler_vacuum",
+    "scheduler_create_backup",
+    "scheduler_restore_backup",
+    "scheduler_reset_database",
+    "scheduler_cron_matches",
+    "scheduler_get_system_timezone",
+    "scheduler_set_timezone_all",
+    "scheduler_normalize_cron_expressions",
+    "scheduler_find_broken_tasks",
+];
+
+#[cfg(target_os = "macos")]
+const PLATFORM_COMMANDS: &[&str] = &[
+    "set_tray_click_through_checked",
+    "set_tray_tooltip",
+    "set_tray_icon",
+];
+
+#[cfg(not(target_os = "macos"))]
+const PLATFORM_COMMANDS: &[&str] = &[];
+
+/// 当前平台实际注册的命令名；调用不存在的命令只会得到 "command not found"，前端应先查这里
+#[tauri::command]
+fn get_available_commands() -> Vec<String> {
+    SHARED_COMMANDS
+        .iter()
+        .chain(PLATFORM_COMMANDS)
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// 快捷键选择器用：试注册后立即注销，能注册成功才算可用；已被本应用占用的也返回 false
+#[tauri::command]
+fn is_shortcut_available(app: tauri::AppHandle, accelerator: String) -> Result<bool, String> {
+    let shortcut: Shortcut = accelerator
+        .parse()
+        .map_err(|e| format!("invalid accelerator {accelerator:?}: {e}"))?;
+    let manager = app.global_shortcut();
+    if manager.is_registered(shortcut) || manager.register(shortcut).is_err() {
+        return Ok(false);
+    }
+    let _ = manager.unregister(shortcut);
+    Ok(true)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let builder = tauri::Builder::default();
@@ -40,26 +223,210 @@ pub fn run() {
     #[cfg(target_os = "macos")]
     let builder = builder.invoke_handler(tauri::generate_handler![
         set_tray_click_through_checked,
+        set_tray_tooltip,
+        set_tray_icon,
+        get_available_commands,
+        is_shortcut_available,
+        window::set_visible_on_all_workspaces,
+        window::set_click_through_mode,
+        window::set_click_through_regions,
+        window::update_click_through_hover,
+        window::set_developer_mode,
+        window::get_developer_mode,
+        window::toggle_devtools,
+        window::window_list_monitors,
+        window::window_move_to_monitor,
+        window::set_window_decorations,
+        window::set_window_level,
         scheduler::scheduler_create_task,
+        scheduler::scheduler_list_presets,
+        scheduler::scheduler_create_from_preset,
+        scheduler::scheduler_import_crontab,
+        scheduler::scheduler_validate_bundle,
+        scheduler::scheduler_normalize_config,
         scheduler::scheduler_get_task,
+        scheduler::scheduler_get_tasks_by_ids,
+        scheduler::scheduler_describe_task,
+        scheduler::scheduler_get_effective_schedule,
         scheduler::scheduler_get_all_tasks,
+        scheduler::scheduler_get_countdowns,
+        scheduler::scheduler_get_agenda,
+        scheduler::scheduler_get_hourly_histogram,
+        scheduler::scheduler_export_ics,
+        scheduler::scheduler_estimate_daily_load,
+        scheduler::scheduler_search_tasks,
+        scheduler::scheduler_find_tasks_referencing,
+        scheduler::scheduler_reorder_tasks,
         scheduler::scheduler_update_task,
+        scheduler::scheduler_preview_update,
         scheduler::scheduler_delete_task,
+        scheduler::scheduler_delete_tasks,
+        scheduler::scheduler_delete_where,
         scheduler::scheduler_enable_task,
+        scheduler::scheduler_disable_after_next_run,
+        scheduler::scheduler_debug_loop,
+        scheduler::scheduler_toggle_exclusive,
         scheduler::scheduler_execute_now,
-        scheduler::scheduler_get_executions
+        scheduler::scheduler_test_action,
+        scheduler::scheduler_replay_execution,
+        scheduler::scheduler_set_next_run,
+        scheduler::scheduler_snooze_all,
+        scheduler::scheduler_confirm_execution,
+        scheduler::scheduler_complete_execution,
+        scheduler::scheduler_report_idle,
+        scheduler::scheduler_report_power_state,
+        scheduler::scheduler_report_event,
+        scheduler::scheduler_subscribe_task,
+        scheduler::scheduler_unsubscribe_task,
+        scheduler::scheduler_tick_now,
+        scheduler::scheduler_set_tick_interval,
+        scheduler::scheduler_get_tick_interval,
+        scheduler::scheduler_get_tick_diagnostics,
+        scheduler::scheduler_get_runtime_state,
+        scheduler::scheduler_debug_compute,
+        scheduler::scheduler_simulate_range,
+        scheduler::scheduler_get_safe_mode,
+        scheduler::scheduler_exit_safe_mode,
+        scheduler::scheduler_get_executions,
+        scheduler::scheduler_get_execution_result,
+        scheduler::scheduler_get_latest_executions,
+        scheduler::scheduler_get_reliability,
+        scheduler::scheduler_get_dispatch_stats,
+        scheduler::scheduler_get_stale_tasks,
+        scheduler::scheduler_reassign_executions,
+        scheduler::scheduler_clear_executions,
+        scheduler::scheduler_find_orphaned_executions,
+        scheduler::scheduler_purge_orphaned_executions,
+        scheduler::scheduler_get_audit,
+        scheduler::scheduler_set_dnd,
+        scheduler::scheduler_get_dnd,
+        scheduler::scheduler_set_log_level,
+        scheduler::scheduler_get_log_path,
+        scheduler::scheduler_set_mock_time,
+        scheduler::scheduler_get_rate_limits,
+        scheduler::scheduler_set_rate_limits,
+        scheduler::scheduler_get_notification_rate_limit,
+        scheduler::scheduler_set_notification_rate_limit,
+        scheduler::scheduler_get_config,
+        scheduler::scheduler_set_config,
+        scheduler::scheduler_get_disabled_action_types,
+        scheduler::scheduler_set_disabled_action_types,
+        scheduler::scheduler_set_max_enabled_tasks,
+        scheduler::scheduler_check_integrity,
+        scheduler::scheduler_self_test,
+        scheduler::scheduler_reload,
+        scheduler::scheduler_vacuum,
+        scheduler::scheduler_create_backup,
+        scheduler::scheduler_restore_backup,
+        scheduler::scheduler_reset_database,
+        scheduler::scheduler_cron_matches,
+        scheduler::scheduler_get_system_timezone,
+        scheduler::scheduler_set_timezone_all,
+        scheduler::scheduler_normalize_cron_expressions,
+        scheduler::scheduler_find_broken_tasks
     ]);
 
     #[cfg(not(target_os = "macos"))]
     let builder = builder.invoke_handler(tauri::generate_handler![
+        get_available_commands,
+        is_shortcut_available,
+        window::set_visible_on_all_workspaces,
+        window::set_click_through_mode,
+        window::set_click_through_regions,
+        window::update_click_through_hover,
+        window::set_developer_mode,
+        window::get_developer_mode,
+        window::toggle_devtools,
+        window::window_list_monitors,
+        window::window_move_to_monitor,
+        window::set_window_decorations,
+        window::set_window_level,
         scheduler::scheduler_create_task,
+        scheduler::scheduler_list_presets,
+        scheduler::scheduler_create_from_preset,
+        scheduler::scheduler_import_crontab,
+        scheduler::scheduler_validate_bundle,
+        scheduler::scheduler_normalize_config,
         scheduler::scheduler_get_task,
+        scheduler::scheduler_get_tasks_by_ids,
+        scheduler::scheduler_describe_task,
+        scheduler::scheduler_get_effective_schedule,
         scheduler::scheduler_get_all_tasks,
+        scheduler::scheduler_get_countdowns,
+        scheduler::scheduler_get_agenda,
+        scheduler::scheduler_get_hourly_histogram,
+        scheduler::scheduler_export_ics,
+        scheduler::scheduler_estimate_daily_load,
+        scheduler::scheduler_search_tasks,
+        scheduler::scheduler_find_tasks_referencing,
+        scheduler::scheduler_reorder_tasks,
         scheduler::scheduler_update_task,
+        scheduler::scheduler_preview_update,
         scheduler::scheduler_delete_task,
+        scheduler::scheduler_delete_tasks,
+        scheduler::scheduler_delete_where,
         scheduler::scheduler_enable_task,
+        scheduler::scheduler_disable_after_next_run,
+        scheduler::scheduler_debug_loop,
+        scheduler::scheduler_toggle_exclusive,
         scheduler::scheduler_execute_now,
-        scheduler::scheduler_get_executions
+        scheduler::scheduler_test_action,
+        scheduler::scheduler_replay_execution,
+        scheduler::scheduler_set_next_run,
+        scheduler::scheduler_snooze_all,
+        scheduler::scheduler_confirm_execution,
+        scheduler::scheduler_complete_execution,
+        scheduler::scheduler_report_idle,
+        scheduler::scheduler_report_power_state,
+        scheduler::scheduler_report_event,
+        scheduler::scheduler_subscribe_task,
+        scheduler::scheduler_unsubscribe_task,
+        scheduler::scheduler_tick_now,
+        scheduler::scheduler_set_tick_interval,
+        scheduler::scheduler_get_tick_interval,
+        scheduler::scheduler_get_tick_diagnostics,
+        scheduler::scheduler_get_runtime_state,
+        scheduler::scheduler_debug_compute,
+        scheduler::scheduler_simulate_range,
+        scheduler::scheduler_get_safe_mode,
+        scheduler::scheduler_exit_safe_mode,
+        scheduler::scheduler_get_executions,
+        scheduler::scheduler_get_execution_result,
+        scheduler::scheduler_get_latest_executions,
+        scheduler::scheduler_get_reliability,
+        scheduler::scheduler_get_dispatch_stats,
+        scheduler::scheduler_get_stale_tasks,
+        scheduler::scheduler_reassign_executions,
+        scheduler::scheduler_clear_executions,
+        scheduler::scheduler_find_orphaned_executions,
+        scheduler::scheduler_purge_orphaned_executions,
+        scheduler::scheduler_get_audit,
+        scheduler::scheduler_set_dnd,
+        scheduler::scheduler_get_dnd,
+        scheduler::scheduler_set_log_level,
+        scheduler::scheduler_get_log_path,
+        scheduler::scheduler_set_mock_time,
+        scheduler::scheduler_get_rate_limits,
+        scheduler::scheduler_set_rate_limits,
+        scheduler::scheduler_get_notification_rate_limit,
+        scheduler::scheduler_set_notification_rate_limit,
+        scheduler::scheduler_get_config,
+        scheduler::scheduler_set_config,
+        scheduler::scheduler_get_disabled_action_types,
+        scheduler::scheduler_set_disabled_action_types,
+        scheduler::scheduler_set_max_enabled_tasks,
+        scheduler::scheduler_check_integrity,
+        scheduler::scheduler_self_test,
+        scheduler::scheduler_reload,
+        scheduler::scheduler_vacuum,
+        scheduler::scheduler_create_backup,
+        scheduler::scheduler_restore_backup,
+        scheduler::scheduler_reset_database,
+        scheduler::scheduler_cron_matches,
+        scheduler::scheduler_get_system_timezone,
+        scheduler::scheduler_set_timezone_all,
+        scheduler::scheduler_normalize_cron_expressions,
+        scheduler::scheduler_find_broken_tasks
     ]);
 
     builder
@@ -73,11 +440,18 @@ pub fn run() {
         .plugin(tauri_plugin_http::init())
         .setup(|app| {
             let window = app.get_webview_window("main").unwrap();
+            let window_prefs = window::load_prefs(app.handle());
+            window::apply_prefs(&window, &window_prefs);
+            app.manage(window::ClickThroughState::default());
 
-            // 后台调度器（轮询 due tasks 并发事件给前端）
-            let scheduler = scheduler::SchedulerRunner::new(app.handle().clone());
-            scheduler.start();
-            app.manage(scheduler);
+            if let Err(err) = logging::init(app.handle()) {
+                eprintln!("[Logging] failed to init file logger: {err}");
+            }
+
+            // 后台调度器（轮询 due tasks 并发事件给前端）；先 manage 再启动，
+            // 后台循环里要通过 app.state 取运行时状态
+            app.manage(scheduler::SchedulerRunner::new(app.handle().clone()));
+            app.state::<scheduler::SchedulerRunner>().start();
 
             #[cfg(debug_assertions)]
             {
@@ -98,6 +472,14 @@ pub fn run() {
                 )?;
                 let click_through_enabled =
                     std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+                let all_workspaces_item = CheckMenuItem::with_id(
+                    app,
+                    "tray_all_workspaces",
+                    "在所有桌面显示",
+                    true,
+                    window_prefs.visible_on_all_workspaces,
+                    None::<&str>,
+                )?;
                 let toggle_visibility_item = MenuItem::with_id(
                     app,
                     "tray_toggle_visibility",
@@ -110,17 +492,19 @@ pub fn run() {
                 let tray_menu = MenuBuilder::new(app)
                     .item(&open_settings_item)
                     .item(&click_through_item)
+                    .item(&all_workspaces_item)
                     .item(&PredefinedMenuItem::separator(app)?)
                     .item(&toggle_visibility_item)
                     .item(&quit_item)
                     .build()?;
 
-                app.manage(TrayState {
-                    click_through_item: click_through_item.clone(),
-                    click_through_enabled: click_through_enabled.clone(),
-                });
+                // 菜单项的所有权会移进下面的事件闭包，先留一份给托管状态
+                let click_through_item_handle = click_through_item.clone();
+                let all_workspaces_item_handle = all_workspaces_item.clone();
+                let click_through_enabled_handle = click_through_enabled.clone();
 
-                TrayIconBuilder::new()
+                // 保留托盘句柄，set_tray_tooltip / set_tray_icon 运行时还要用
+                let tray = TrayIconBuilder::new()
                     .icon(TRAY_ICON)
                     .icon_as_template(true)
                     .tooltip("AI Desktop Pet")
@@ -134,7 +518,10 @@ pub fn run() {
                         match id {
                             "tray_open_settings" => {
                                 // 打开设置前，强制关闭穿透，避免无法操作设置窗口
-                                let _ = main_window.set_ignore_cursor_events(false);
+                                let _ = window::apply_click_through_mode(
+                                    app,
+                                    window::ClickThroughMode::Off,
+                                );
                                 let _ = click_through_item.set_checked(false);
                                 click_through_enabled
                                     .store(false, std::sync::atomic::Ordering::Relaxed);
@@ -150,13 +537,29 @@ pub fn run() {
                             "tray_click_through" => {
                                 let enabled = !click_through_enabled
                                     .fetch_xor(true, std::sync::atomic::Ordering::Relaxed);
-                                let _ = main_window.set_ignore_cursor_events(enabled);
+                                let _ = window::apply_click_through_mode(
+                                    app,
+                                    if enabled {
+                                        window::ClickThroughMode::Full
+                                    } else {
+                                        window::ClickThroughMode::Off
+                                    },
+                                );
                                 let _ = click_through_item.set_checked(enabled);
                                 let _ = app.emit(
                                     "click-through-changed",
                                     serde_json::json!({ "enabled": enabled }),
                                 );
                             }
+                            "tray_all_workspaces" => {
+                                let enabled = !window::load_prefs(app).visible_on_all_workspaces;
+                                if window::apply_visible_on_all_workspaces(app, enabled).is_ok() {
+                                    let _ = all_workspaces_item.set_checked(enabled);
+                                } else {
+                                    // 失败时回滚勾选状态，保持与真实窗口行为一致
+                                    let _ = all_workspaces_item.set_checked(!enabled);
+                                }
+                            }
                             "tray_toggle_visibility" => {
                                 let is_visible = main_window.is_visible().unwrap_or(true);
                                 if is_visible {
@@ -173,11 +576,19 @@ pub fn run() {
                         }
                     })
                     .build(app)?;
+
+                app.manage(TrayState {
+                    click_through_item: click_through_item_handle,
+                    all_workspaces_item: all_workspaces_item_handle,
+                    click_through_enabled: click_through_enabled_handle,
+                    tray,
+                });
             }
 
             // macOS-specific: Set window to be transparent with vibrancy
+            // 用户通过 set_window_decorations 打开了标题栏时保持原样
             #[cfg(target_os = "macos")]
-            {
+            if !window_prefs.decorations {
                 // Set the window background to transparent
                 window.set_decorations(false)?;
 
@@ -188,6 +599,12 @@ pub fn run() {
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // 只有走到这里才算正常退出；崩溃时运行标记会留下，下次启动进入安全模式
+            if let tauri::RunEvent::Exit = event {
+                scheduler::end_session(app);
+            }
+        });
 }
@@ -0,0 +1,125 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use tauri::{AppHandle, Manager};
+
+const LOG_FILE_NAME: &str = "scheduler.log";
+
+// 单个日志文件上限，超过后滚动为 scheduler.log.1 … scheduler.log.N
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+const MAX_LOG_BACKUPS: usize = 3;
+
+static LOGGER: OnceLock<FileLogger> = OnceLock::new();
+
+struct FileLogger {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileLogger {
+    fn open(path: &Path) -> Option<File> {
+        OpenOptions::new().create(true).append(true).open(path).ok()
+    }
+
+    fn rotate_if_needed(&self, file: &mut Option<File>) {
+        let too_large = std::fs::metadata(&self.path)
+            .map(|m| m.len() >= MAX_LOG_BYTES)
+            .unwrap_or(false);
+        if !too_large {
+            return;
+        }
+
+        // 先关掉当前句柄，Windows 上不能重命名打开中的文件
+        *file = None;
+        for i in (1..MAX_LOG_BACKUPS).rev() {
+            let _ = std::fs::rename(backup_path(&self.path, i), backup_path(&self.path, i + 1));
+        }
+        let _ = std::fs::rename(&self.path, backup_path(&self.path, 1));
+        *file = Self::open(&self.path);
+    }
+}
+
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} [{}] {}: {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        // debug 构建保留 stderr 输出，方便 `pnpm tauri dev` 时直接看
+        if cfg!(debug_assertions) {
+            eprint!("{line}");
+        }
+
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        self.rotate_if_needed(&mut file);
+        if file.is_none() {
+            *file = Self::open(&self.path);
+        }
+        if let Some(f) = file.as_mut() {
+            let _ = f.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(f) = file.as_mut() {
+                let _ = f.flush();
+            }
+        }
+    }
+}
+
+pub fn log_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("failed to resolve app_data_dir: {e}"))?;
+    std::fs::create_dir_all(&base_dir)
+        .map_err(|e| format!("failed to create dir {base_dir:?}: {e}"))?;
+    Ok(base_dir.join(LOG_FILE_NAME))
+}
+
+/// 安装全局 logger；重复调用是安全的（只有第一次生效）
+pub fn init(app: &AppHandle) -> Result<(), String> {
+    let path = log_path(app)?;
+    let logger = LOGGER.get_or_init(|| FileLogger {
+        file: Mutex::new(FileLogger::open(&path)),
+        path,
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+    Ok(())
+}
+
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level.trim().parse::<LevelFilter>().map_err(|_| {
+        format!("unknown log level: {level} (expected off/error/warn/info/debug/trace)")
+    })
+}
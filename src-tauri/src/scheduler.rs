@@ -1,24 +1,123 @@
 use std::{
-    path::Path,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Mutex,
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+        Mutex, MutexGuard,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use cron::Schedule;
-use rusqlite::{params, Connection, OptionalExtension};
+use icu_calendar::{cal::ChineseTraditional, types::LeapStatus, Date as IcuDate};
+use rusqlite::{params, params_from_iter, Connection, DatabaseName, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter, Manager};
+use tauri::{ipc::Channel, AppHandle, Emitter, Manager, State};
 use uuid::Uuid;
 
 const DB_FILE_NAME: &str = "pet.db";
+const RESULTS_DIR_NAME: &str = "results";
+// 调度器运行期间存在，正常退出时删除；启动时还在说明上次是崩溃退出的
+const SESSION_MARKER_FILE_NAME: &str = "scheduler.running";
 
 // 轮询间隔：任务调度不需要毫秒级精度，降低 CPU 唤醒
 const SCHEDULER_TICK_MS: u64 = 1_000;
+// scheduler_set_tick_interval 允许的范围；下限防止 0 或过小的值让后台循环空转占满 CPU
+const MIN_TICK_INTERVAL_MS: u64 = 50;
+const MAX_TICK_INTERVAL_MS: u64 = 60_000;
+
+// 当前轮询间隔；持久化在 scheduler_settings 里，启动和每轮 tick 时从库里同步
+static TICK_INTERVAL_MS: AtomicU64 = AtomicU64::new(SCHEDULER_TICK_MS);
+
+fn tick_interval_ms() -> u64 {
+    TICK_INTERVAL_MS.load(Ordering::Relaxed)
+}
+
+// tick 连续失败时的退避上限，以及每累计多少次失败向前端发一次 scheduler_error
+const SCHEDULER_MAX_BACKOFF_MS: u64 = 60_000;
+const SCHEDULER_ERROR_EMIT_THRESHOLD: u32 = 5;
+// 墙上时间相对单调时钟倒退超过这个值，视为系统时间被往回调了
+const CLOCK_JUMP_THRESHOLD_MS: i64 = 2 * 60 * 1000;
+
+// 限流默认值：interval 最小 5 秒；全局每分钟最多 60 次执行（0 表示不限制）
+const DEFAULT_MIN_INTERVAL_SECONDS: i64 = 5;
+const DEFAULT_MAX_EXECUTIONS_PER_MINUTE: i64 = 60;
+
+// compute_next_run 是纯函数，拿不到连接；下限值由 tick/命令从 settings 同步到这里
+static MIN_INTERVAL_SECONDS: AtomicI64 = AtomicI64::new(DEFAULT_MIN_INTERVAL_SECONDS);
+
+// 前端通过 scheduler_report_power_state 上报；配合 pauseOnBattery 设置在 tick 里暂停非关键任务
+static ON_BATTERY: AtomicBool = AtomicBool::new(false);
+
+// 超过这么久没有上报，视为前端已停止上报，不再认为处于空闲
+const IDLE_REPORT_STALE_MS: i64 = 2 * 60 * 1000;
+
+// 连续失败达到这么多次后不再逐次发 task_failed，改为节流的 task_failing_repeatedly
+const FAILURE_COALESCE_THRESHOLD: i64 = 3;
+// 与上一次执行相隔超过这个时间的失败，连续失败计数重新开始
+const FAILURE_COALESCE_WINDOW_MS: i64 = 60 * 60 * 1000;
+// 同一任务两次 task_failing_repeatedly 之间的最小间隔
+const FAILURE_COALESCE_EMIT_INTERVAL_MS: i64 = 10 * 60 * 1000;
+
+struct CachedPreview {
+    // 计算时任务的 updated_at，任务被修改后缓存失效
+    updated_at: Option<i64>,
+    next_run: Option<i64>,
+}
+
+// 停用任务的预览时间（task id -> 缓存），见 preview_next_run
+static PREVIEW_CACHE: Mutex<BTreeMap<String, CachedPreview>> = Mutex::new(BTreeMap::new());
+
+// scheduler_debug_loop 开启的调试循环（task id -> 截止时间），期间每个 tick 都视为到期
+static DEBUG_LOOPS: Mutex<BTreeMap<String, i64>> = Mutex::new(BTreeMap::new());
+
+const DEBUG_LOOP_MAX_SECONDS: i64 = 10 * 60;
+
+// 到期超过这么久才算错过（misfire），正常 tick 的延迟不受 misfirePolicy 影响
+const MISFIRE_GRACE_MS: i64 = 60 * 1000;
+// misfirePolicy 为 run_all 时单次最多补跑多少次，睡了几周的短间隔任务不会补成千上万次
+const CATCHUP_MAX_RUNS: usize = 500;
+// 补跑每完成这么多次发一次 scheduler_catchup_progress
+const CATCHUP_PROGRESS_EVERY: usize = 10;
+
+// 正在后台补跑的任务，同一任务不会同时开两轮补跑
+static CATCHUP_RUNNING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// 正在执行中的任务（task id -> 并发次数）。tick 之外 execute_now / report_idle 也会执行任务，
+// overlapPolicy 依赖它判断上一次是否还没结束
+static IN_FLIGHT: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+struct InFlightGuard(String);
+
+impl InFlightGuard {
+    fn enter(task_id: &str) -> Self {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|p| p.into_inner());
+        *in_flight.entry(task_id.to_string()).or_insert(0) += 1;
+        Self(task_id.to_string())
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some(count) = in_flight.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                in_flight.remove(&self.0);
+            }
+        }
+    }
+}
+
+fn is_in_flight(task_id: &str) -> bool {
+    IN_FLIGHT
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .contains_key(task_id)
+}
 
 #[derive(Clone)]
 pub struct SchedulerRunner {
@@ -26,6 +125,91 @@ pub struct SchedulerRunner {
     is_started: std::sync::Arc<AtomicBool>,
     stop: std::sync::Arc<AtomicBool>,
     join: std::sync::Arc<Mutex<Option<tauri::async_runtime::JoinHandle<()>>>>,
+    // tick 执行期间持有；需要独占数据库的操作（如恢复备份）也拿这把锁
+    tick_lock: std::sync::Arc<Mutex<()>>,
+    // 安全模式：上次异常退出后暂停自动调度，等用户确认后再恢复
+    safe_mode: std::sync::Arc<AtomicBool>,
+    // 后台循环最近一次转完一圈的时间（调度器时钟），自检用来判断循环是否还活着
+    last_loop_ms: std::sync::Arc<AtomicI64>,
+    tick_stats: std::sync::Arc<Mutex<TickStats>>,
+    state: std::sync::Arc<SchedulerState>,
+    clock: &'static dyn Clock,
+}
+
+/// 调度器的运行时状态（不落库）。perform_action 等处拿不到 SchedulerRunner 的引用，
+/// 通过 `runtime_state(app)` 取得
+#[derive(Default)]
+struct SchedulerState {
+    // 全局通知令牌桶，限额由 tick/命令从 settings 同步进来
+    notification_bucket: Mutex<NotificationBucket>,
+    // 最近一次 scheduler_report_idle 上报的空闲秒数及上报时间，供组合触发器的 idle 条件判断
+    last_idle_seconds: AtomicI64,
+    last_idle_reported_at: AtomicI64,
+    // 前端通过 scheduler_report_event 上报的事件名 -> 最近一次时间，供组合触发器的 event 条件判断
+    seen_events: Mutex<BTreeMap<String, i64>>,
+    // 单个任务的生命周期订阅（task id -> 前端传入的 channel），详情页实时查看用
+    task_subscribers: Mutex<BTreeMap<String, Vec<Channel<serde_json::Value>>>>,
+    // 已发过 task_failing_repeatedly 的任务（task id -> 最近一次发送时间），成功后清除
+    failing_notified_at: Mutex<BTreeMap<String, i64>>,
+}
+
+impl SchedulerState {
+    fn record_idle(&self, idle_seconds: i64, now_ms: i64) {
+        self.last_idle_seconds
+            .store(idle_seconds, Ordering::Relaxed);
+        self.last_idle_reported_at.store(now_ms, Ordering::Relaxed);
+    }
+
+    /// 上报之后的时间也算进空闲时长；太久没上报则视为不空闲
+    fn idle_seconds(&self, now_ms: i64) -> i64 {
+        let reported_at = self.last_idle_reported_at.load(Ordering::Relaxed);
+        if reported_at == 0 || now_ms - reported_at > IDLE_REPORT_STALE_MS {
+            return 0;
+        }
+        self.last_idle_seconds.load(Ordering::Relaxed) + (now_ms - reported_at).max(0) / 1000
+    }
+
+    fn record_event(&self, event_name: String, now_ms: i64) {
+        self.seen_events
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(event_name, now_ms);
+    }
+
+    /// 距上次 task_failing_repeatedly 已超过 FAILURE_COALESCE_EMIT_INTERVAL_MS 时记下这次并返回 true
+    fn claim_failing_notification(&self, task_id: &str, now_ms: i64) -> bool {
+        let mut notified = self
+            .failing_notified_at
+            .lock()
+            .unwrap_or_else(|p| p.into_inner());
+        if notified
+            .get(task_id)
+            .is_some_and(|&at| now_ms - at < FAILURE_COALESCE_EMIT_INTERVAL_MS)
+        {
+            return false;
+        }
+        notified.insert(task_id.to_string(), now_ms);
+        true
+    }
+
+    fn clear_failing_notification(&self, task_id: &str) {
+        self.failing_notified_at
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .remove(task_id);
+    }
+
+    fn event_seen_since(&self, event_name: &str, since_ms: i64) -> bool {
+        self.seen_events
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .get(event_name)
+            .is_some_and(|&at| at >= since_ms)
+    }
+}
+
+fn runtime_state(app: &AppHandle) -> std::sync::Arc<SchedulerState> {
+    app.state::<SchedulerRunner>().state.clone()
 }
 
 impl SchedulerRunner {
@@ -35,28 +219,105 @@ impl SchedulerRunner {
             is_started: std::sync::Arc::new(AtomicBool::new(false)),
             stop: std::sync::Arc::new(AtomicBool::new(false)),
             join: std::sync::Arc::new(Mutex::new(None)),
+            tick_lock: std::sync::Arc::new(Mutex::new(())),
+            safe_mode: std::sync::Arc::new(AtomicBool::new(false)),
+            last_loop_ms: std::sync::Arc::new(AtomicI64::new(0)),
+            tick_stats: std::sync::Arc::new(Mutex::new(TickStats::default())),
+            state: std::sync::Arc::new(SchedulerState::default()),
+            clock: default_clock(),
         }
     }
 
+    /// 阻止后台 tick 运行，直到返回的 guard 被释放
+    pub fn pause_ticks(&self) -> MutexGuard<'_, ()> {
+        self.tick_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
     pub fn start(&self) {
         if self.is_started.swap(true, Ordering::SeqCst) {
             return;
         }
 
+        if begin_session(&self.app) {
+            log::warn!("[Scheduler] previous run did not shut down cleanly, starting in safe mode");
+            self.safe_mode.store(true, Ordering::SeqCst);
+            let _ = self.app.emit("scheduler_safe_mode", ());
+        }
+
         let app = self.app.clone();
         let stop = self.stop.clone();
         let join = self.join.clone();
+        let tick_lock = self.tick_lock.clone();
+        let safe_mode = self.safe_mode.clone();
+        let last_loop_ms = self.last_loop_ms.clone();
+        let tick_stats = self.tick_stats.clone();
+        let clock = self.clock;
 
-        let handle = tauri::async_runtime::spawn_blocking(move || loop {
-            if stop.load(Ordering::Relaxed) {
-                break;
+        let handle = tauri::async_runtime::spawn_blocking(move || {
+            if let Err(err) = apply_persisted_log_level(&app) {
+                log::warn!("[Scheduler] failed to load log level: {err}");
             }
-
-            if let Err(err) = tick(&app) {
-                eprintln!("[Scheduler] tick error: {err}");
+            if let Err(err) = open_db(&app).and_then(|conn| {
+                ensure_tables(&conn)?;
+                load_tick_interval_ms(&conn)
+            }) {
+                log::warn!("[Scheduler] failed to load tick interval: {err}");
             }
 
-            std::thread::sleep(Duration::from_millis(SCHEDULER_TICK_MS));
+            let mut consecutive_errors: u32 = 0;
+            let mut jump_detector = ClockJumpDetector::default();
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let loop_ms = clock.now_ms();
+                last_loop_ms.store(loop_ms, Ordering::Relaxed);
+                tick_stats
+                    .lock()
+                    .unwrap_or_else(|p| p.into_inner())
+                    .record(loop_ms);
+                if safe_mode.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(tick_interval_ms()));
+                    continue;
+                }
+
+                let result = {
+                    let _guard = tick_lock
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    let now = clock.now_ms();
+                    if let Some(backwards_ms) = jump_detector.observe(now) {
+                        if let Err(err) = handle_clock_jump(&app, now, backwards_ms) {
+                            log::error!("[Scheduler] failed to handle clock jump: {err}");
+                        }
+                    }
+                    tick(&app, clock)
+                };
+
+                match result {
+                    Ok(_) => consecutive_errors = 0,
+                    Err(err) => {
+                        consecutive_errors = consecutive_errors.saturating_add(1);
+                        log::error!(
+                            "[Scheduler] tick error ({consecutive_errors} in a row): {err}"
+                        );
+                        // 打包后的应用看不到 stderr，持续失败时通知前端
+                        if consecutive_errors.is_multiple_of(SCHEDULER_ERROR_EMIT_THRESHOLD) {
+                            let _ = app.emit(
+                                "scheduler_error",
+                                serde_json::json!({
+                                    "error": err,
+                                    "consecutiveErrors": consecutive_errors,
+                                }),
+                            );
+                        }
+                    }
+                }
+
+                std::thread::sleep(Duration::from_millis(tick_backoff_ms(consecutive_errors)));
+            }
         });
 
         *join.lock().expect("scheduler join lock poisoned") = Some(handle);
@@ -75,748 +336,7329 @@ impl SchedulerRunner {
     }
 }
 
+fn session_marker_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("failed to resolve app_data_dir: {e}"))?;
+    ensure_dir(&base_dir)?;
+    Ok(base_dir.join(SESSION_MARKER_FILE_NAME))
+}
+
+/// 写入运行标记，返回上次是否异常退出（标记还在）
+fn begin_session(app: &AppHandle) -> bool {
+    let path = match session_marker_path(app) {
+        Ok(path) => path,
+        Err(err) => {
+            log::warn!("[Scheduler] failed to resolve session marker: {err}");
+            return false;
+        }
+    };
+    let crashed = path.exists();
+    if let Err(err) = std::fs::write(&path, now_ms().to_string()) {
+        log::warn!("[Scheduler] failed to write session marker: {err}");
+    }
+    crashed
+}
+
+/// 应用正常退出时调用，删除运行标记
+pub fn end_session(app: &AppHandle) {
+    if let Ok(path) = session_marker_path(app) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
 impl Drop for SchedulerRunner {
     fn drop(&mut self) {
         self.stop();
     }
 }
 
-fn tick(app: &AppHandle) -> Result<(), String> {
-    let now_ms = now_ms();
-    let conn = open_db(app)?;
-    ensure_tables(&conn)?;
+// 两圈之间超过这个间隔，基本只能是机器睡眠/挂起（出错退避最长也只有 SCHEDULER_MAX_BACKOFF_MS）
+const SLEEP_GAP_THRESHOLD_MS: i64 = 2 * SCHEDULER_MAX_BACKOFF_MS as i64;
 
-    let due_tasks = list_due_tasks(&conn, now_ms)?;
-    for task in due_tasks {
-        if let Err(err) = execute_task(app, &conn, &task) {
-            eprintln!("[Scheduler] execute_task error: {err}");
+/// 后台循环相邻两圈之间的间隔统计，用来解释"夜里任务没跑"是不是因为机器睡着了
+#[derive(Default)]
+struct TickStats {
+    last_ms: Option<i64>,
+    total_ticks: u64,
+    total_gap_ms: i64,
+    longest_gap_ms: i64,
+    missed_estimate: i64,
+}
+
+impl TickStats {
+    fn record(&mut self, now_ms: i64) {
+        if let Some(last) = self.last_ms {
+            let gap = (now_ms - last).max(0);
+            self.total_gap_ms += gap;
+            self.longest_gap_ms = self.longest_gap_ms.max(gap);
+            if gap > SLEEP_GAP_THRESHOLD_MS {
+                self.missed_estimate += gap / tick_interval_ms() as i64 - 1;
+            }
         }
+        self.last_ms = Some(now_ms);
+        self.total_ticks += 1;
     }
-
-    Ok(())
 }
 
-fn now_ms() -> i64 {
-    Utc::now().timestamp_millis()
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TickDiagnostics {
+    pub longest_gap_ms: i64,
+    pub avg_gap_ms: f64,
+    pub total_ticks: u64,
+    /// 睡眠期间本应执行却没执行的 tick 数（粗略估计）
+    pub missed_estimate: i64,
 }
 
-fn open_db(app: &AppHandle) -> Result<Connection, String> {
-    let base_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("failed to resolve app_data_dir: {e}"))?;
-    ensure_dir(&base_dir)?;
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DebugCompute {
+    pub next_run_ms: Option<i64>,
+    /// 按解析出的时区格式化的 RFC 3339 时间
+    pub next_run_iso: Option<String>,
+    /// cron 实际使用的 6 段表达式
+    pub effective_expression: Option<String>,
+    pub resolved_timezone: Option<String>,
+    pub error: Option<String>,
+}
 
-    let db_path = base_dir.join(DB_FILE_NAME);
-    Connection::open(db_path).map_err(|e| format!("failed to open sqlite db: {e}"))
+/// 用单调时钟校验墙上时间：两次观察之间墙上时间比单调时钟少走了多少
+#[derive(Default)]
+struct ClockJumpDetector {
+    last: Option<(i64, Instant)>,
 }
 
-fn ensure_dir(path: &Path) -> Result<(), String> {
-    std::fs::create_dir_all(path).map_err(|e| format!("failed to create dir {path:?}: {e}"))
+impl ClockJumpDetector {
+    /// 墙上时间倒退超过阈值时返回倒退的毫秒数
+    fn observe(&mut self, wall_ms: i64) -> Option<i64> {
+        let now = Instant::now();
+        let jump = self.last.and_then(|(last_wall, last_instant)| {
+            let elapsed = i64::try_from(now.duration_since(last_instant).as_millis()).ok()?;
+            let backwards = last_wall + elapsed - wall_ms;
+            (wall_ms < last_wall && backwards > CLOCK_JUMP_THRESHOLD_MS).then_some(backwards)
+        });
+        self.last = Some((wall_ms, now));
+        jump
+    }
 }
 
-fn ensure_tables(conn: &Connection) -> Result<(), String> {
-    // 注意：前端也会初始化 schema；这里做兜底，保证 scheduler 可独立工作
-    conn.execute_batch(
-        r#"
-CREATE TABLE IF NOT EXISTS tasks (
-    id TEXT PRIMARY KEY,
-    name TEXT NOT NULL,
-    description TEXT,
-    trigger_type TEXT NOT NULL,
-    trigger_config TEXT NOT NULL,
-    action_type TEXT NOT NULL,
-    action_config TEXT NOT NULL,
-    enabled INTEGER DEFAULT 1,
-    last_run INTEGER,
-    next_run INTEGER,
-    metadata TEXT,
-    created_at INTEGER NOT NULL,
-    updated_at INTEGER
-);
+/// 系统时间往回调之后，按新的当前时间重排所有已排期任务，避免按旧时间算出的 next_run 错乱
+fn handle_clock_jump(app: &AppHandle, now_ms: i64, backwards_ms: i64) -> Result<(), String> {
+    log::warn!("[Scheduler] system clock went backwards by {backwards_ms}ms, rescheduling tasks");
 
-CREATE TABLE IF NOT EXISTS task_executions (
-    id TEXT PRIMARY KEY,
-    task_id TEXT NOT NULL,
-    status TEXT NOT NULL,
-    started_at INTEGER NOT NULL,
-    completed_at INTEGER,
-    result TEXT,
-    error TEXT,
-    duration INTEGER,
-    FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
-);
+    let mut conn = open_db(app)?;
+    ensure_tables(&conn)?;
 
-CREATE INDEX IF NOT EXISTS idx_tasks_next_run ON tasks(next_run, enabled);
-CREATE INDEX IF NOT EXISTS idx_tasks_enabled ON tasks(enabled);
-CREATE INDEX IF NOT EXISTS idx_executions_task ON task_executions(task_id);
-CREATE INDEX IF NOT EXISTS idx_executions_status ON task_executions(status);
+    let tasks = {
+        let mut stmt = conn
+            .prepare(
+                r#"
+SELECT id, trigger_type, trigger_config, metadata
+FROM tasks
+WHERE enabled = 1 AND next_run IS NOT NULL
 "#,
-    )
-    .map_err(|e| format!("failed to ensure tables: {e}"))?;
+            )
+            .map_err(|e| format!("failed to prepare scheduled tasks: {e}"))?;
+        let rows = stmt
+            .query_map([], |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get::<_, String>(2)?,
+                    r.get::<_, Option<String>>(3)?,
+                ))
+            })
+            .map_err(|e| format!("failed to query scheduled tasks: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("scheduled task map error: {e}"))?
+    };
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    for (id, trigger_type, trigger_config, metadata) in &tasks {
+        let next_run = compute_next_run(trigger_type, trigger_config, metadata.as_deref(), now_ms);
+        tx.execute(
+            "UPDATE tasks SET next_run = ?, updated_at = ? WHERE id = ?",
+            params![next_run, now_ms, id],
+        )
+        .map_err(|e| format!("failed to reschedule task: {e}"))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    let _ = app.emit(
+        "scheduler_clock_jump",
+        serde_json::json!({
+            "backwardsMs": backwards_ms,
+            "rescheduled": tasks.len(),
+        }),
+    );
     Ok(())
 }
 
-#[derive(Debug, Clone)]
-struct DbTaskRow {
-    id: String,
-    name: String,
-    description: Option<String>,
-    trigger_type: String,
-    trigger_config: String,
-    action_type: String,
-    action_config: String,
-    enabled: bool,
-    last_run: Option<i64>,
-    next_run: Option<i64>,
-    metadata: Option<String>,
-    created_at: i64,
-    updated_at: Option<i64>,
+/// 连续出错时指数退避（封顶 SCHEDULER_MAX_BACKOFF_MS），并加一点抖动避免固定节奏地撞同一个错误
+fn tick_backoff_ms(consecutive_errors: u32) -> u64 {
+    if consecutive_errors == 0 {
+        return tick_interval_ms();
+    }
+    let base = tick_interval_ms()
+        .saturating_mul(1u64 << consecutive_errors.min(16))
+        .min(SCHEDULER_MAX_BACKOFF_MS);
+    let jitter = u64::from(Utc::now().timestamp_subsec_millis()) % (base / 10 + 1);
+    base + jitter
 }
 
-fn row_to_api_task(row: DbTaskRow) -> ApiTask {
-    ApiTask {
-        id: row.id,
-        name: row.name,
-        description: row.description,
-        trigger: ApiTrigger {
-            r#type: row.trigger_type,
-            config: row.trigger_config,
-        },
-        action: ApiAction {
-            r#type: row.action_type,
-            config: row.action_config,
-        },
-        enabled: row.enabled,
-        last_run: row.last_run,
-        next_run: row.next_run,
-        metadata: row.metadata.and_then(|m| serde_json::from_str(&m).ok()),
-        created_at: row.created_at,
-        updated_at: row.updated_at,
-    }
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TickSummary {
+    pub fired: i64,
+    pub errors: Vec<String>,
 }
 
-fn list_due_tasks(conn: &Connection, now_ms: i64) -> Result<Vec<DbTaskRow>, String> {
-    let mut stmt = conn
-        .prepare(
-            r#"
-SELECT
-  id, name, description,
-  trigger_type, trigger_config,
-  action_type, action_config,
-  enabled, last_run, next_run, metadata,
-  created_at, updated_at
-FROM tasks
-WHERE enabled = 1 AND next_run IS NOT NULL AND next_run <= ?
-ORDER BY next_run ASC
-LIMIT 20
-"#,
-        )
-        .map_err(|e| format!("failed to prepare due task query: {e}"))?;
+fn tick(app: &AppHandle, clock: &dyn Clock) -> Result<TickSummary, String> {
+    let now_ms = clock.now_ms();
+    let conn = open_db(app)?;
+    ensure_tables(&conn)?;
+    let state = runtime_state(app);
 
-    let rows = stmt
-        .query_map(params![now_ms], |r| {
-            Ok(DbTaskRow {
-                id: r.get(0)?,
-                name: r.get(1)?,
-                description: r.get(2)?,
-                trigger_type: r.get(3)?,
-                trigger_config: r.get(4)?,
-                action_type: r.get(5)?,
-                action_config: r.get(6)?,
-                enabled: r.get::<_, i64>(7)? == 1,
-                last_run: r.get(8)?,
-                next_run: r.get(9)?,
-                metadata: r.get(10)?,
-                created_at: r.get(11)?,
-                updated_at: r.get(12)?,
-            })
-        })
-        .map_err(|e| format!("failed to query due tasks: {e}"))?;
+    let limits = load_rate_limits(&conn)?;
+    load_notification_rate_limit(app, &conn)?;
+    load_tick_interval_ms(&conn)?;
+    expire_pending_confirmations(&conn, now_ms)?;
 
-    let mut out = Vec::new();
-    for row in rows {
-        out.push(row.map_err(|e| format!("failed to map due task: {e}"))?);
+    let mut due_tasks = list_due_tasks(&conn, now_ms)?;
+    let debug_ids = active_debug_loops(now_ms);
+    for id in &debug_ids {
+        if due_tasks.iter().any(|t| &t.id == id) {
+            continue;
+        }
+        if let Some(task) = get_db_task(&conn, id)?.filter(|t| t.enabled) {
+            due_tasks.push(task);
+        }
     }
-    Ok(out)
-}
+    let mut budget = if limits.max_executions_per_minute > 0 {
+        Some(
+            limits
+                .max_executions_per_minute
+                .saturating_sub(count_recent_executions(&conn, now_ms - 60_000)?),
+        )
+    } else {
+        None
+    };
 
-fn compute_next_run(trigger_type: &str, trigger_config: &str, from_ms: i64) -> Option<i64> {
-    match trigger_type {
-        "interval" => {
-            let cfg = serde_json::from_str::<IntervalTriggerConfig>(trigger_config).ok()?;
-            if cfg.seconds <= 0 {
-                return None;
+    let paused_on_battery = ON_BATTERY.load(Ordering::Relaxed) && load_pause_on_battery(&conn)?;
+
+    let mut summary = TickSummary::default();
+    let mut deferred = Vec::new();
+    for task in due_tasks {
+        let options = task_options(task.metadata.as_deref());
+        // 调试循环每个 tick 都会捡到任务，条件不满足时静默跳过，不每轮写一条 deferred 记录
+        let in_debug_loop = debug_ids.contains(&task.id);
+        if options.requires_confirmation {
+            // 不执行也不推进 next_run；pending 记录存在期间 list_due_tasks 不会再捡到它
+            if let Err(err) = request_confirmation(app, &conn, &task, now_ms) {
+                log::error!("[Scheduler] failed to request confirmation: {err}");
             }
-            Some(from_ms + (cfg.seconds as i64) * 1000)
+            continue;
         }
-        "cron" => {
-            let cfg = serde_json::from_str::<CronTriggerConfig>(trigger_config).ok()?;
-            cron_next_ms(&cfg.expression, from_ms)
+        if task.trigger_type == "composite" && !composite_conditions_met(&state, &task, now_ms) {
+            if in_debug_loop {
+                continue;
+            }
+            if let Err(err) = record_skipped_execution(
+                &conn,
+                &task,
+                now_ms,
+                "deferred",
+                "composite trigger conditions not met",
+            ) {
+                log::error!("[Scheduler] failed to defer composite task: {err}");
+            }
+            continue;
+        }
+        if paused_on_battery && !options.run_on_battery {
+            if in_debug_loop {
+                continue;
+            }
+            if let Err(err) = record_skipped_execution(
+                &conn,
+                &task,
+                now_ms,
+                "deferred",
+                "paused while on battery",
+            ) {
+                log::error!("[Scheduler] failed to defer task on battery: {err}");
+            }
+            continue;
+        }
+        if is_in_flight(&task.id) {
+            // 调试循环不管 overlapPolicy，上一次没跑完就等下个 tick，避免越堆越多
+            if in_debug_loop {
+                continue;
+            }
+            match options.overlap_policy.as_deref() {
+                Some("skip") => {
+                    if let Err(err) = record_skipped_execution(
+                        &conn,
+                        &task,
+                        now_ms,
+                        "skipped",
+                        "previous run still in progress",
+                    ) {
+                        log::error!("[Scheduler] failed to skip overlapping run: {err}");
+                    }
+                    continue;
+                }
+                // next_run 不动，上一次跑完后的 tick 会再捡起来
+                Some("queue") => continue,
+                _ => {}
+            }
+        }
+        if !in_debug_loop
+            && task
+                .next_run
+                .is_some_and(|next| now_ms - next > MISFIRE_GRACE_MS)
+        {
+            match options.misfire_policy.as_deref() {
+                Some("skip") => {
+                    if let Err(err) = record_skipped_execution(
+                        &conn,
+                        &task,
+                        now_ms,
+                        "skipped",
+                        "missed while the scheduler was not running",
+                    ) {
+                        log::error!("[Scheduler] failed to skip misfired run: {err}");
+                    }
+                    continue;
+                }
+                Some("run_all") => match start_catchup(app, &conn, &task, now_ms) {
+                    Ok(true) => continue,
+                    Ok(false) => {}
+                    Err(err) => {
+                        log::error!("[Scheduler] failed to start catch-up: {err}");
+                        summary.errors.push(format!("{}: {err}", task.id));
+                        continue;
+                    }
+                },
+                _ => {}
+            }
+        }
+        if let Some(remaining) = budget.as_mut() {
+            if *remaining <= 0 {
+                // 超出全局配额：保留 next_run 不动，下个 tick 再捡起来
+                deferred.push(task.id);
+                continue;
+            }
+            *remaining -= 1;
+        }
+        match execute_task(app, &conn, &task) {
+            Ok(()) => summary.fired += 1,
+            Err(err) => {
+                log::error!("[Scheduler] execute_task error: {err}");
+                summary.errors.push(format!("{}: {err}", task.id));
+            }
         }
-        "manual" | "event" => None,
-        _ => None,
     }
-}
 
-fn cron_next_ms(expr_5: &str, from_ms: i64) -> Option<i64> {
-    // TS 侧定义是 5 段 cron（分 时 日 月 周），这里补一个秒字段
-    let expr_6 = format!("0 {expr_5}");
-    let schedule = Schedule::from_str(&expr_6).ok()?;
-    let from_dt: DateTime<Utc> = Utc.timestamp_millis_opt(from_ms).single()?;
-    schedule
-        .after(&from_dt)
-        .next()
-        .map(|dt| dt.timestamp_millis())
+    if !deferred.is_empty() {
+        let _ = app.emit(
+            "scheduler_rate_limited",
+            serde_json::json!({
+                "deferred": deferred,
+                "maxExecutionsPerMinute": limits.max_executions_per_minute,
+            }),
+        );
+    }
+
+    Ok(summary)
 }
 
-fn execute_task(app: &AppHandle, conn: &Connection, task: &DbTaskRow) -> Result<(), String> {
-    let start_ms = now_ms();
+/// event 条件只认上次执行（没有则是创建）之后上报的事件
+fn composite_conditions_met(state: &SchedulerState, task: &DbTaskRow, now_ms: i64) -> bool {
+    let since = task.last_run.unwrap_or(task.created_at);
+    match TriggerConfig::from_db(&task.trigger_type, &task.trigger_config) {
+        TriggerConfig::Invalid { .. } => false,
+        trigger => trigger_condition_met(state, &trigger, since, now_ms),
+    }
+}
 
-    let exec_id = Uuid::new_v4().to_string();
+/// 本轮不执行的任务（重叠跳过 / 电池暂停）：留一条执行记录说明原因，并把 next_run 推到下一个周期
+fn record_skipped_execution(
+    conn: &Connection,
+    task: &DbTaskRow,
+    now_ms: i64,
+    status: &str,
+    reason: &str,
+) -> Result<(), String> {
     conn.execute(
         r#"
-INSERT INTO task_executions (id, task_id, status, started_at)
-VALUES (?, ?, 'running', ?)
+INSERT INTO task_executions (id, task_id, status, started_at, completed_at, error, duration)
+VALUES (?, ?, ?, ?, ?, ?, 0)
 "#,
-        params![exec_id, task.id, start_ms],
+        params![
+            Uuid::new_v4().to_string(),
+            task.id,
+            status,
+            now_ms,
+            now_ms,
+            reason
+        ],
     )
-    .map_err(|e| format!("failed to insert execution: {e}"))?;
+    .map_err(|e| format!("failed to insert skipped execution: {e}"))?;
 
-    let _ = app.emit("task_started", task.id.clone());
+    let next_run = compute_next_run(
+        &task.trigger_type,
+        &task.trigger_config,
+        task.metadata.as_deref(),
+        now_ms,
+    );
+    conn.execute(
+        "UPDATE tasks SET next_run = ?, updated_at = ? WHERE id = ?",
+        params![next_run, now_ms, task.id],
+    )
+    .map_err(|e| format!("failed to advance skipped task: {e}"))?;
+    Ok(())
+}
 
-    let mut status = "success".to_string();
-    let mut result_json: Option<String> = None;
-    let mut error: Option<String> = None;
+/// 从 next_run 起到 now_ms 为止错过的触发次数（含 next_run 本身），最多 CATCHUP_MAX_RUNS
+fn missed_fires(task: &DbTaskRow, now_ms: i64) -> usize {
+    let mut count = 0;
+    let mut at = task.next_run;
+    while let Some(fire) = at.filter(|&t| t <= now_ms) {
+        count += 1;
+        if count >= CATCHUP_MAX_RUNS {
+            log::warn!(
+                "[Scheduler] task {} missed at least {CATCHUP_MAX_RUNS} runs, catch-up is capped",
+                task.id
+            );
+            break;
+        }
+        at = compute_next_run(
+            &task.trigger_type,
+            &task.trigger_config,
+            task.metadata.as_deref(),
+            fire,
+        );
+    }
+    count
+}
 
-    match task.action_type.as_str() {
-        "notification" => {
-            match serde_json::from_str::<NotificationActionConfig>(&task.action_config) {
-                Ok(cfg) => {
-                    let payload = serde_json::json!({
-                        "title": cfg.title,
-                        "body": cfg.body,
-                        "actionButton": cfg.action_button,
-                        "actionCallback": cfg.action_callback,
-                    });
-                    let _ = app.emit("task_notification", payload.clone());
-                    result_json = Some(payload.to_string());
-                }
-                Err(e) => {
-                    status = "failed".to_string();
-                    error = Some(format!("invalid notification action config: {e}"));
-                }
-            }
+/// misfirePolicy 为 run_all 且错过了多次：先把 next_run 推到下一个周期，再交给后台线程池逐个补跑，
+/// 不阻塞当前 tick。只错过一次时返回 false，照常执行
+fn start_catchup(
+    app: &AppHandle,
+    conn: &Connection,
+    task: &DbTaskRow,
+    now_ms: i64,
+) -> Result<bool, String> {
+    let total = missed_fires(task, now_ms);
+    if total <= 1 {
+        return Ok(false);
+    }
+    if !CATCHUP_RUNNING
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .insert(task.id.clone())
+    {
+        return Ok(true);
+    }
+
+    let next_run = compute_next_run(
+        &task.trigger_type,
+        &task.trigger_config,
+        task.metadata.as_deref(),
+        now_ms,
+    );
+    if let Err(e) = conn.execute(
+        "UPDATE tasks SET next_run = ?, updated_at = ? WHERE id = ?",
+        params![next_run, now_ms, task.id],
+    ) {
+        finish_catchup(&task.id);
+        return Err(format!("failed to advance misfired task: {e}"));
+    }
+
+    log::info!(
+        "[Scheduler] task {} missed {total} runs, catching up",
+        task.id
+    );
+    let app = app.clone();
+    let task_id = task.id.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        let mut done = 0;
+        let result = run_catchup(&app, &task_id, total, &mut done);
+        finish_catchup(&task_id);
+        if let Err(err) = &result {
+            log::error!(
+                "[Scheduler] catch-up for task {task_id} stopped after {done}/{total}: {err}"
+            );
         }
-        "agent_task" => match serde_json::from_str::<AgentTaskActionConfig>(&task.action_config) {
-            Ok(cfg) => {
-                let payload = serde_json::json!({
-                    "prompt": cfg.prompt,
-                    "toolsAllowed": cfg.tools_allowed,
-                    "maxSteps": cfg.max_steps,
-                });
-                let _ = app.emit("task_agent_execute", payload.clone());
-                result_json = Some(payload.to_string());
-            }
-            Err(e) => {
-                status = "failed".to_string();
-                error = Some(format!("invalid agent_task action config: {e}"));
-            }
-        },
-        "workflow" => match serde_json::from_str::<WorkflowActionConfig>(&task.action_config) {
-            Ok(cfg) => {
-                let payload = serde_json::json!({
-                    "workflowId": cfg.workflow_id,
-                    "input": cfg.input,
-                });
-                let _ = app.emit("task_workflow_execute", payload.clone());
-                result_json = Some(payload.to_string());
-            }
-            Err(e) => {
-                status = "failed".to_string();
-                error = Some(format!("invalid workflow action config: {e}"));
-            }
-        },
-        "script" => {
-            status = "failed".to_string();
-            error = Some("script action is not supported yet".to_string());
+        let _ = app.emit(
+            "scheduler_catchup_complete",
+            serde_json::json!({
+                "taskId": task_id,
+                "done": done,
+                "total": total,
+                "error": result.err(),
+            }),
+        );
+    });
+    Ok(true)
+}
+
+fn finish_catchup(task_id: &str) {
+    CATCHUP_RUNNING
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .remove(task_id);
+}
+
+/// 逐个补跑错过的触发。每次补跑都拿 tick 锁，与后台 tick 串行；超出每分钟执行配额时等配额恢复，
+/// 通知照常经过全局令牌桶。任务被停用/删除、调度器停止或进入安全模式时提前结束
+fn run_catchup(
+    app: &AppHandle,
+    task_id: &str,
+    total: usize,
+    done: &mut usize,
+) -> Result<(), String> {
+    let conn = open_db(app)?;
+    ensure_tables(&conn)?;
+
+    while *done < total {
+        let runner = app.try_state::<SchedulerRunner>();
+        if runner
+            .as_ref()
+            .is_some_and(|r| r.stop.load(Ordering::Relaxed) || r.safe_mode.load(Ordering::SeqCst))
+        {
+            return Err("scheduler stopped".to_string());
         }
-        other => {
-            status = "failed".to_string();
-            error = Some(format!("unknown action type: {other}"));
+
+        let limits = load_rate_limits(&conn)?;
+        if limits.max_executions_per_minute > 0
+            && count_recent_executions(&conn, now_ms() - 60_000)?
+                >= limits.max_executions_per_minute
+        {
+            std::thread::sleep(Duration::from_millis(tick_interval_ms()));
+            continue;
+        }
+
+        {
+            let _paused = runner.as_ref().map(|r| r.pause_ticks());
+            let Some(task) = get_db_task(&conn, task_id)?.filter(|t| t.enabled) else {
+                return Err("task was disabled or deleted".to_string());
+            };
+            execute_task(app, &conn, &task)?;
+        }
+        *done += 1;
+
+        if done.is_multiple_of(CATCHUP_PROGRESS_EVERY) || *done == total {
+            let _ = app.emit(
+                "scheduler_catchup_progress",
+                serde_json::json!({ "taskId": task_id, "done": *done, "total": total }),
+            );
         }
     }
+    Ok(())
+}
 
-    let end_ms = now_ms();
-    let duration = end_ms.saturating_sub(start_ms);
+fn request_confirmation(
+    app: &AppHandle,
+    conn: &Connection,
+    task: &DbTaskRow,
+    now_ms: i64,
+) -> Result<(), String> {
+    let exec_id = Uuid::new_v4().to_string();
+    conn.execute(
+        r#"
+INSERT INTO task_executions (id, task_id, status, started_at)
+VALUES (?, ?, 'pending_confirmation', ?)
+"#,
+        params![exec_id, task.id, now_ms],
+    )
+    .map_err(|e| format!("failed to insert pending execution: {e}"))?;
+
+    let _ = app.emit(
+        "task_awaiting_confirmation",
+        serde_json::json!({
+            "taskId": task.id,
+            "taskName": task.name,
+            "executionId": exec_id,
+            "expiresAt": now_ms + load_confirmation_timeout_ms(conn)?,
+        }),
+    );
+    Ok(())
+}
+
+/// 超时未确认的记录标为 expired，并把任务推到下一个周期，避免立刻又弹一次确认
+fn expire_pending_confirmations(conn: &Connection, now_ms: i64) -> Result<(), String> {
+    let cutoff = now_ms - load_confirmation_timeout_ms(conn)?;
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT id, task_id FROM task_executions
+WHERE status = 'pending_confirmation' AND started_at <= ?
+"#,
+        )
+        .map_err(|e| format!("failed to prepare pending query: {e}"))?;
+    let rows = stmt
+        .query_map(params![cutoff], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("failed to query pending executions: {e}"))?;
+    let mut expired = Vec::new();
+    for row in rows {
+        expired.push(row.map_err(|e| format!("pending execution map error: {e}"))?);
+    }
+    drop(stmt);
+
+    for (exec_id, task_id) in expired {
+        finish_unconfirmed(conn, &exec_id, &task_id, "expired", now_ms)?;
+    }
+    Ok(())
+}
 
+/// pending 记录以 declined/expired 收尾，并推进任务的 next_run
+fn finish_unconfirmed(
+    conn: &Connection,
+    exec_id: &str,
+    task_id: &str,
+    status: &str,
+    now_ms: i64,
+) -> Result<(), String> {
     conn.execute(
+        "UPDATE task_executions SET status = ?, completed_at = ? WHERE id = ?",
+        params![status, now_ms, exec_id],
+    )
+    .map_err(|e| format!("failed to update pending execution: {e}"))?;
+
+    if let Some(task) = get_db_task(conn, task_id)? {
+        let next_run = if task.enabled {
+            compute_next_run(
+                &task.trigger_type,
+                &task.trigger_config,
+                task.metadata.as_deref(),
+                now_ms,
+            )
+        } else {
+            None
+        };
+        conn.execute(
+            "UPDATE tasks SET next_run = ?, updated_at = ? WHERE id = ?",
+            params![next_run, now_ms, task_id],
+        )
+        .map_err(|e| format!("failed to advance task: {e}"))?;
+    }
+    Ok(())
+}
+
+fn count_recent_executions(conn: &Connection, since_ms: i64) -> Result<i64, String> {
+    // 没有真正执行的记录不占全局配额
+    conn.query_row(
         r#"
-UPDATE task_executions
-SET status = ?, completed_at = ?, result = ?, error = ?, duration = ?
-WHERE id = ?
+SELECT COUNT(*) FROM task_executions
+WHERE started_at >= ?
+  AND status NOT IN (
+    'skipped', 'deferred', 'pending_confirmation', 'declined', 'expired', 'blocked',
+    'throttled'
+  )
 "#,
-        params![status, end_ms, result_json, error, duration, exec_id],
+        params![since_ms],
+        |r| r.get(0),
     )
-    .map_err(|e| format!("failed to update execution: {e}"))?;
+    .map_err(|e| format!("failed to count recent executions: {e}"))
+}
 
-    // 更新任务的 last_run/next_run
-    let next_run = compute_next_run(&task.trigger_type, &task.trigger_config, end_ms);
+/// 调度器读取"当前时间"的入口；测试构建可换成 MockClock，让调度结果可复现
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> i64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+/// 可手动拨动的时钟；值 <= 0 表示跟随系统时间
+#[cfg(any(test, feature = "mock-clock"))]
+pub struct MockClock {
+    now_ms: AtomicI64,
+}
+
+#[cfg(any(test, feature = "mock-clock"))]
+impl MockClock {
+    pub const fn new() -> Self {
+        Self {
+            now_ms: AtomicI64::new(0),
+        }
+    }
+
+    pub fn set(&self, ms: i64) {
+        self.now_ms.store(ms, Ordering::Relaxed);
+    }
+}
+
+#[cfg(any(test, feature = "mock-clock"))]
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(any(test, feature = "mock-clock"))]
+impl Clock for MockClock {
+    fn now_ms(&self) -> i64 {
+        match self.now_ms.load(Ordering::Relaxed) {
+            ms if ms > 0 => ms,
+            _ => SystemClock.now_ms(),
+        }
+    }
+}
+
+#[cfg(feature = "mock-clock")]
+static MOCK_CLOCK: MockClock = MockClock::new();
+
+fn default_clock() -> &'static dyn Clock {
+    #[cfg(feature = "mock-clock")]
+    {
+        &MOCK_CLOCK
+    }
+
+    #[cfg(not(feature = "mock-clock"))]
+    {
+        &SystemClock
+    }
+}
+
+fn now_ms() -> i64 {
+    default_clock().now_ms()
+}
+
+/// 超出上限的执行结果写到 app_data_dir/results/<exec_id>.json，数据库里只留截断后的前缀
+fn spill_result(
+    app: &AppHandle,
+    exec_id: &str,
+    full: &str,
+    max_bytes: usize,
+) -> Result<(String, Option<String>), String> {
+    if full.len() <= max_bytes {
+        return Ok((full.to_string(), None));
+    }
+
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("failed to resolve app_data_dir: {e}"))?
+        .join(RESULTS_DIR_NAME);
+    ensure_dir(&dir)?;
+    let path = dir.join(format!("{exec_id}.json"));
+    std::fs::write(&path, full).map_err(|e| format!("failed to write result file: {e}"))?;
+
+    let mut end = max_bytes;
+    while !full.is_char_boundary(end) {
+        end -= 1;
+    }
+    Ok((
+        full[..end].to_string(),
+        Some(path.to_string_lossy().into_owned()),
+    ))
+}
+
+fn db_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("failed to resolve app_data_dir: {e}"))?;
+    ensure_dir(&base_dir)?;
+    Ok(base_dir.join(DB_FILE_NAME))
+}
+
+fn open_db(app: &AppHandle) -> Result<Connection, String> {
+    let conn =
+        Connection::open(db_path(app)?).map_err(|e| format!("failed to open sqlite db: {e}"))?;
+    configure_connection(&conn)?;
+    Ok(conn)
+}
+
+fn configure_connection(conn: &Connection) -> Result<(), String> {
+    // SQLite 默认不校验外键，不打开的话 ON DELETE CASCADE 不会生效
+    conn.execute_batch("PRAGMA foreign_keys = ON")
+        .map_err(|e| format!("failed to enable foreign keys: {e}"))?;
+
+    // 在事务内或编译时关闭了外键支持时，上面的 PRAGMA 会静默失效，这里回读确认
+    let enabled: i64 = conn
+        .query_row("PRAGMA foreign_keys", [], |r| r.get(0))
+        .map_err(|e| format!("failed to read foreign_keys pragma: {e}"))?;
+    if enabled != 1 {
+        return Err("sqlite foreign key enforcement could not be enabled".to_string());
+    }
+    Ok(())
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|m| m.len())
+}
+
+fn ensure_dir(path: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(path).map_err(|e| format!("failed to create dir {path:?}: {e}"))
+}
+
+fn ensure_tables(conn: &Connection) -> Result<(), String> {
+    // 注意：前端也会初始化 schema；这里做兜底，保证 scheduler 可独立工作
+    conn.execute_batch(
+        r#"
+CREATE TABLE IF NOT EXISTS tasks (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    trigger_type TEXT NOT NULL,
+    trigger_config TEXT NOT NULL,
+    action_type TEXT NOT NULL,
+    action_config TEXT NOT NULL,
+    enabled INTEGER DEFAULT 1,
+    last_run INTEGER,
+    next_run INTEGER,
+    metadata TEXT,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS task_executions (
+    id TEXT PRIMARY KEY,
+    task_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    completed_at INTEGER,
+    result TEXT,
+    error TEXT,
+    duration INTEGER,
+    FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS scheduler_settings (
+    key TEXT PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id TEXT PRIMARY KEY,
+    task_id TEXT NOT NULL,
+    event TEXT NOT NULL,
+    detail TEXT,
+    at INTEGER NOT NULL,
+    FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_next_run ON tasks(next_run, enabled);
+CREATE INDEX IF NOT EXISTS idx_tasks_enabled ON tasks(enabled);
+CREATE INDEX IF NOT EXISTS idx_executions_task ON task_executions(task_id);
+CREATE INDEX IF NOT EXISTS idx_executions_status ON task_executions(status);
+CREATE INDEX IF NOT EXISTS idx_audit_task ON audit_log(task_id, at);
+"#,
+    )
+    .map_err(|e| format!("failed to ensure tables: {e}"))?;
+
+    // 旧库补列：CREATE TABLE IF NOT EXISTS 不会给已存在的表加新列
+    ensure_column(conn, "task_executions", "slow", "INTEGER DEFAULT 0")?;
+    ensure_column(conn, "task_executions", "result_path", "TEXT")?;
+    ensure_column(conn, "task_executions", "config_snapshot", "TEXT")?;
+    ensure_column(conn, "task_executions", "dispatch_ms", "INTEGER")?;
+    ensure_column(conn, "tasks", "sort_order", "INTEGER")?;
+    ensure_column(conn, "tasks", "last_idle_fired", "INTEGER")?;
+    ensure_column(conn, "tasks", "priority", "INTEGER NOT NULL DEFAULT 0")?;
+    ensure_column(
+        conn,
+        "tasks",
+        "consecutive_failures",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    ensure_column(
+        conn,
+        "tasks",
+        "disable_after_run",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    Ok(())
+}
+
+fn ensure_column(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<(), String> {
+    let exists: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM pragma_table_info('{table}') WHERE name = ?"),
+            params![column],
+            |r| r.get(0),
+        )
+        .map_err(|e| format!("failed to inspect {table} columns: {e}"))?;
+    if exists == 0 {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))
+            .map_err(|e| format!("failed to add column {table}.{column}: {e}"))?;
+    }
+    Ok(())
+}
+
+fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM scheduler_settings WHERE key = ?",
+        params![key],
+        |r| r.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("failed to read setting {key}: {e}"))
+}
+
+fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
     conn.execute(
         r#"
-UPDATE tasks
-SET last_run = ?, next_run = ?, updated_at = ?
-WHERE id = ?
+INSERT INTO scheduler_settings (key, value, updated_at)
+VALUES (?, ?, ?)
+ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at
 "#,
-        params![end_ms, next_run, end_ms, task.id],
+        params![key, value, now_ms()],
     )
-    .map_err(|e| format!("failed to update task run info: {e}"))?;
+    .map_err(|e| format!("failed to write setting {key}: {e}"))?;
+    Ok(())
+}
+
+fn delete_setting(conn: &Connection, key: &str) -> Result<(), String> {
+    conn.execute("DELETE FROM scheduler_settings WHERE key = ?", params![key])
+        .map_err(|e| format!("failed to delete setting {key}: {e}"))?;
+    Ok(())
+}
+
+/// 记录任务状态变化（启用/停用等），方便排查"任务为什么不跑了"
+fn record_audit(
+    conn: &Connection,
+    task_id: &str,
+    event: &str,
+    detail: Option<&str>,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO audit_log (id, task_id, event, detail, at) VALUES (?, ?, ?, ?, ?)",
+        params![Uuid::new_v4().to_string(), task_id, event, detail, now_ms()],
+    )
+    .map_err(|e| format!("failed to write audit log: {e}"))?;
+    Ok(())
+}
+
+fn enabled_audit_event(enabled: bool) -> &'static str {
+    if enabled {
+        "enabled"
+    } else {
+        "disabled"
+    }
+}
+
+const LOG_LEVEL_SETTING_KEY: &str = "log_level";
+
+fn apply_persisted_log_level(app: &AppHandle) -> Result<(), String> {
+    let conn = open_db(app)?;
+    ensure_tables(&conn)?;
+    if let Some(level) = get_setting(&conn, LOG_LEVEL_SETTING_KEY)? {
+        log::set_max_level(crate::logging::parse_level(&level)?);
+    }
+    Ok(())
+}
+
+const DND_SETTING_KEY: &str = "dnd";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DndState {
+    pub enabled: bool,
+    pub until_ms: Option<i64>,
+}
+
+fn load_dnd(conn: &Connection, now_ms: i64) -> Result<DndState, String> {
+    let state = get_setting(conn, DND_SETTING_KEY)?
+        .and_then(|raw| serde_json::from_str::<DndState>(&raw).ok())
+        .unwrap_or(DndState {
+            enabled: false,
+            until_ms: None,
+        });
+
+    // 到期自动解除，避免残留的 DND 一直吞掉通知
+    if state.enabled && state.until_ms.is_some_and(|until| until <= now_ms) {
+        delete_setting(conn, DND_SETTING_KEY)?;
+        return Ok(DndState {
+            enabled: false,
+            until_ms: None,
+        });
+    }
+    Ok(state)
+}
+
+const RATE_LIMITS_SETTING_KEY: &str = "rate_limits";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimits {
+    pub min_interval_seconds: i64,
+    pub max_executions_per_minute: i64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            min_interval_seconds: DEFAULT_MIN_INTERVAL_SECONDS,
+            max_executions_per_minute: DEFAULT_MAX_EXECUTIONS_PER_MINUTE,
+        }
+    }
+}
+
+fn load_rate_limits(conn: &Connection) -> Result<RateLimits, String> {
+    let limits = get_setting(conn, RATE_LIMITS_SETTING_KEY)?
+        .and_then(|raw| serde_json::from_str::<RateLimits>(&raw).ok())
+        .unwrap_or_default();
+    MIN_INTERVAL_SECONDS.store(limits.min_interval_seconds, Ordering::Relaxed);
+    Ok(limits)
+}
+
+#[derive(Debug, Clone)]
+struct DbTaskRow {
+    id: String,
+    name: String,
+    description: Option<String>,
+    trigger_type: String,
+    trigger_config: String,
+    action_type: String,
+    action_config: String,
+    enabled: bool,
+    last_run: Option<i64>,
+    next_run: Option<i64>,
+    metadata: Option<String>,
+    created_at: i64,
+    updated_at: Option<i64>,
+    priority: i64,
+}
+
+const TASK_COLUMNS: &str = "id, name, description, trigger_type, trigger_config, \
+     action_type, action_config, enabled, last_run, next_run, metadata, \
+     created_at, updated_at, priority";
+
+fn task_from_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<DbTaskRow> {
+    Ok(DbTaskRow {
+        id: r.get(0)?,
+        name: r.get(1)?,
+        description: r.get(2)?,
+        trigger_type: r.get(3)?,
+        trigger_config: r.get(4)?,
+        action_type: r.get(5)?,
+        action_config: r.get(6)?,
+        enabled: r.get::<_, i64>(7)? == 1,
+        last_run: r.get(8)?,
+        next_run: r.get(9)?,
+        metadata: r.get(10)?,
+        created_at: r.get(11)?,
+        updated_at: r.get(12)?,
+        priority: r.get(13)?,
+    })
+}
+
+/// 停用任务"如果现在启用"的下一次时间，不写回库。lunar 等触发器算起来不便宜，
+/// 按 updated_at 缓存，算出的时间过去后再重算
+fn preview_next_run(row: &DbTaskRow, now: i64) -> Option<i64> {
+    if row.enabled {
+        return row.next_run;
+    }
+    let mut cache = PREVIEW_CACHE.lock().unwrap_or_else(|p| p.into_inner());
+    if let Some(cached) = cache.get(&row.id) {
+        if cached.updated_at == row.updated_at && cached.next_run.is_none_or(|t| t > now) {
+            return cached.next_run;
+        }
+    }
+    let preview = compute_next_run(
+        &row.trigger_type,
+        &row.trigger_config,
+        row.metadata.as_deref(),
+        now,
+    );
+    cache.insert(
+        row.id.clone(),
+        CachedPreview {
+            updated_at: row.updated_at,
+            next_run: preview,
+        },
+    );
+    preview
+}
+
+/// 与 row_to_api_task 相同，但给停用任务补上 preview_next_run；只在列表/详情接口里用
+fn row_to_api_task_with_preview(row: DbTaskRow, now: i64) -> ApiTask {
+    let preview = preview_next_run(&row, now);
+    ApiTask {
+        preview_next_run: preview,
+        ..row_to_api_task(row)
+    }
+}
+
+fn row_to_api_task(row: DbTaskRow) -> ApiTask {
+    // 停用任务的预览时间由 row_to_api_task_with_preview 按需计算
+    let preview_next_run = if row.enabled { row.next_run } else { None };
+    ApiTask {
+        id: row.id,
+        name: row.name,
+        description: row.description,
+        trigger: ApiTrigger {
+            config: TriggerConfig::from_db(&row.trigger_type, &row.trigger_config),
+            r#type: row.trigger_type,
+        },
+        action: ApiAction {
+            config: ActionConfig::from_db(&row.action_type, &row.action_config),
+            r#type: row.action_type,
+        },
+        enabled: row.enabled,
+        last_run: row.last_run,
+        next_run: row.next_run,
+        metadata: row.metadata.and_then(|m| serde_json::from_str(&m).ok()),
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+        priority: row.priority,
+        preview_next_run,
+    }
+}
+
+fn list_due_tasks(conn: &Connection, now_ms: i64) -> Result<Vec<DbTaskRow>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+SELECT {TASK_COLUMNS}
+FROM tasks
+WHERE enabled = 1 AND next_run IS NOT NULL AND next_run <= ?
+  AND id NOT IN (
+    SELECT task_id FROM task_executions WHERE status = 'pending_confirmation'
+  )
+ORDER BY priority DESC, next_run ASC
+LIMIT 20
+"#
+        ))
+        .map_err(|e| format!("failed to prepare due task query: {e}"))?;
+
+    let rows = stmt
+        .query_map(params![now_ms], task_from_row)
+        .map_err(|e| format!("failed to query due tasks: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row.map_err(|e| format!("failed to map due task: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// 触发器给出的下一次时间，再叠加 metadata.skipRules（跳过周末/指定日期）
+fn compute_next_run(
+    trigger_type: &str,
+    trigger_config: &str,
+    metadata: Option<&str>,
+    from_ms: i64,
+) -> Option<i64> {
+    let mut next = try_compute_next_run(trigger_type, trigger_config, from_ms)
+        .ok()
+        .flatten()?;
+    let Some(rules) = task_options(metadata).skip_rules else {
+        return Some(next);
+    };
+
+    let tz = trigger_timezone(trigger_config);
+    for _ in 0..SKIP_RULES_MAX_STEPS {
+        let date = Utc
+            .timestamp_millis_opt(next)
+            .single()?
+            .with_timezone(&tz)
+            .date_naive();
+        if !rules.skips(date) {
+            return Some(next);
+        }
+        let next_day = date.succ_opt()?;
+        next = if trigger_type == "interval" {
+            // interval 没有"某天的第一个触发时间"，按整周期往后推，保持原来的相位
+            interval_slot_from_day(trigger_config, tz, next, next_day)?
+        } else {
+            // 从下一天零点前重新算，直接拿到那天的第一个触发时间，而不是在被跳过的一天里逐个试
+            let day_start = localize(tz, next_day.and_time(NaiveTime::MIN))?;
+            try_compute_next_run(
+                trigger_type,
+                trigger_config,
+                day_start.timestamp_millis() - 1,
+            )
+            .ok()
+            .flatten()?
+        };
+    }
+
+    log::warn!("[Scheduler] skipRules skipped every slot within {SKIP_RULES_MAX_STEPS} steps");
+    None
+}
+
+/// interval 触发器从 slot_ms 起按整周期往后推，返回落在 day 零点或之后的第一个时间
+fn interval_slot_from_day(
+    trigger_config: &str,
+    tz: Tz,
+    slot_ms: i64,
+    day: NaiveDate,
+) -> Option<i64> {
+    let cfg = serde_json::from_str::<IntervalTriggerConfig>(trigger_config).ok()?;
+    let seconds = cfg
+        .seconds
+        .max(MIN_INTERVAL_SECONDS.load(Ordering::Relaxed));
+    if seconds <= 0 {
+        return None;
+    }
+    let day_start = day.and_time(NaiveTime::MIN);
+    let steps_until = |gap: i64, step: i64| if gap <= 0 { 0 } else { (gap + step - 1) / step };
+    match cfg.interval_mode.as_deref().unwrap_or("elapsed") {
+        "wallclock" => {
+            let local = Utc
+                .timestamp_millis_opt(slot_ms)
+                .single()?
+                .with_timezone(&tz)
+                .naive_local();
+            let steps = steps_until((day_start - local).num_seconds(), seconds);
+            localize(tz, local + chrono::Duration::seconds(steps * seconds))
+                .map(|dt| dt.timestamp_millis())
+        }
+        _ => {
+            let step_ms = seconds * 1000;
+            let start_ms = localize(tz, day_start)?.timestamp_millis();
+            Some(slot_ms + steps_until(start_ms - slot_ms, step_ms) * step_ms)
+        }
+    }
+}
+
+/// 触发器配置里的 timezone（cron/weekly/lunar/interval 都有），没有则用系统时区
+fn trigger_timezone(trigger_config: &str) -> Tz {
+    let name = serde_json::from_str::<serde_json::Value>(trigger_config)
+        .ok()
+        .and_then(|v| v.get("timezone")?.as_str().map(str::to_string));
+    resolve_timezone(name.as_deref()).unwrap_or_else(|_| system_timezone())
+}
+
+/// 与 `compute_next_run` 相同，但保留失败原因；`Ok(None)` 表示该触发器本来就不按时间调度
+fn try_compute_next_run(
+    trigger_type: &str,
+    trigger_config: &str,
+    from_ms: i64,
+) -> Result<Option<i64>, String> {
+    match trigger_type {
+        "interval" => {
+            let cfg = serde_json::from_str::<IntervalTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid interval trigger config: {e}"))?;
+            if cfg.seconds <= 0 {
+                return Err("interval seconds must be > 0".to_string());
+            }
+            let seconds = cfg
+                .seconds
+                .max(MIN_INTERVAL_SECONDS.load(Ordering::Relaxed));
+            match cfg.interval_mode.as_deref().unwrap_or("elapsed") {
+                "elapsed" => Ok(Some(from_ms + seconds * 1000)),
+                "wallclock" => {
+                    // 在本地墙上时间上加，跨夏令时后仍是同一个钟点（86400 秒 = 每天同一时间）
+                    let tz = resolve_timezone(cfg.timezone.as_deref())?;
+                    let local = Utc
+                        .timestamp_millis_opt(from_ms)
+                        .single()
+                        .ok_or_else(|| format!("invalid timestamp: {from_ms}"))?
+                        .with_timezone(&tz)
+                        .naive_local();
+                    Ok(localize(tz, local + chrono::Duration::seconds(seconds))
+                        .map(|dt| dt.timestamp_millis()))
+                }
+                other => Err(format!(
+                    "unknown intervalMode: {other} (expected elapsed/wallclock)"
+                )),
+            }
+        }
+        "cron" => {
+            let cfg = serde_json::from_str::<CronTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid cron trigger config: {e}"))?;
+            let tz = resolve_timezone(cfg.timezone.as_deref())?;
+            let schedule = parse_cron_schedule(&cfg.expression)?;
+            Ok(cron_next_ms(&schedule, tz, from_ms))
+        }
+        "weekly" => {
+            let cfg = serde_json::from_str::<WeeklyTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid weekly trigger config: {e}"))?;
+            weekly_next_ms(&cfg, from_ms)
+        }
+        "lunar" => {
+            let cfg = serde_json::from_str::<LunarTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid lunar trigger config: {e}"))?;
+            lunar_next_ms(&cfg, from_ms)
+        }
+        "solar" => {
+            let cfg = serde_json::from_str::<SolarTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid solar trigger config: {e}"))?;
+            solar_next_ms(&cfg, from_ms)
+        }
+        "idle" => {
+            // 空闲触发由前端上报驱动（scheduler_report_idle），不参与定时排期
+            let cfg = serde_json::from_str::<IdleTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid idle trigger config: {e}"))?;
+            if cfg.idle_seconds < 1 {
+                return Err("idleSeconds must be >= 1".to_string());
+            }
+            Ok(None)
+        }
+        "composite" => {
+            let cfg = serde_json::from_str::<CompositeTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid composite trigger config: {e}"))?;
+            composite_next_ms(&cfg, from_ms, 1)
+        }
+        "manual" | "event" => Ok(None),
+        other => Err(format!("unknown trigger type: {other}")),
+    }
+}
+
+fn composite_next_ms(
+    cfg: &CompositeTriggerConfig,
+    from_ms: i64,
+    depth: usize,
+) -> Result<Option<i64>, String> {
+    if depth > MAX_COMPOSITE_DEPTH {
+        return Err(format!(
+            "composite triggers can be nested at most {MAX_COMPOSITE_DEPTH} levels deep"
+        ));
+    }
+    if cfg.children.is_empty() {
+        return Err("composite trigger needs at least one child".to_string());
+    }
+
+    let mut times = Vec::new();
+    for child in &cfg.children {
+        let next = match child {
+            TriggerConfig::Composite(inner) => composite_next_ms(inner, from_ms, depth + 1)?,
+            TriggerConfig::Manual if cfg.op == CompositeOp::And => {
+                return Err("manual trigger cannot be part of an \"and\" composite".to_string());
+            }
+            other => {
+                let (trigger_type, trigger_config) = other.to_db()?;
+                try_compute_next_run(&trigger_type, &trigger_config, from_ms)?
+            }
+        };
+        times.extend(next);
+    }
+
+    Ok(match cfg.op {
+        CompositeOp::Or => times.into_iter().min(),
+        CompositeOp::And => times.into_iter().max(),
+    })
+}
+
+/// 组合触发器到点时检查非定时的条件；定时类子触发器已经体现在 next_run 里，视为满足
+fn trigger_condition_met(
+    state: &SchedulerState,
+    trigger: &TriggerConfig,
+    since_ms: i64,
+    now_ms: i64,
+) -> bool {
+    match trigger {
+        TriggerConfig::Idle(cfg) => state.idle_seconds(now_ms) >= cfg.idle_seconds,
+        TriggerConfig::Event(cfg) => state.event_seen_since(&cfg.event_name, since_ms),
+        TriggerConfig::Composite(cfg) => match cfg.op {
+            CompositeOp::And => cfg
+                .children
+                .iter()
+                .all(|c| trigger_condition_met(state, c, since_ms, now_ms)),
+            CompositeOp::Or => cfg
+                .children
+                .iter()
+                .any(|c| trigger_condition_met(state, c, since_ms, now_ms)),
+        },
+        _ => true,
+    }
+}
+
+/// 会按时间自动排期的触发器类型
+fn is_time_based_trigger(trigger_type: &str) -> bool {
+    matches!(
+        trigger_type,
+        "interval" | "cron" | "weekly" | "lunar" | "solar" | "composite"
+    )
+}
+
+/// 创建/更新任务时对触发器配置做的前置校验；未覆盖的类型保持原来的宽松行为
+fn validate_trigger_config(trigger_type: &str, trigger_config: &str) -> Result<(), String> {
+    match trigger_type {
+        "weekly" => {
+            let cfg = serde_json::from_str::<WeeklyTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid weekly trigger config: {e}"))?;
+            weekly_next_ms(&cfg, now_ms()).map(|_| ())
+        }
+        "lunar" => {
+            let cfg = serde_json::from_str::<LunarTriggerConfig>(trigger_config)
+                .map_err(|e| format!("invalid lunar trigger config: {e}"))?;
+            lunar_next_ms(&cfg, now_ms()).map(|_| ())
+        }
+        "interval" | "idle" | "solar" => {
+            try_compute_next_run(trigger_type, trigger_config, now_ms()).map(|_| ())
+        }
+        "composite" => {
+            // and 组合里没有任何定时子触发器就永远排不上
+            if try_compute_next_run(trigger_type, trigger_config, now_ms())?.is_none() {
+                return Err("composite trigger has no scheduled child".to_string());
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// 把本地墙上时间落到具体时区：重叠时取较早的那次，夏令时跳过的时间顺延一小时
+fn localize(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    tz.from_local_datetime(&local).earliest().or_else(|| {
+        tz.from_local_datetime(&(local + chrono::Duration::hours(1)))
+            .earliest()
+    })
+}
+
+fn weekly_next_ms(cfg: &WeeklyTriggerConfig, from_ms: i64) -> Result<Option<i64>, String> {
+    if cfg.every_n_weeks < 1 {
+        return Err("everyNWeeks must be >= 1".to_string());
+    }
+    if !(0..=6).contains(&cfg.weekday) {
+        return Err("weekday must be 0-6 (0 = Sunday)".to_string());
+    }
+    let time = NaiveTime::parse_from_str(&cfg.time, "%H:%M")
+        .map_err(|e| format!("invalid time {:?} (expected HH:MM): {e}", cfg.time))?;
+    let anchor = NaiveDate::parse_from_str(&cfg.anchor_date, "%Y-%m-%d").map_err(|e| {
+        format!(
+            "invalid anchorDate {:?} (expected YYYY-MM-DD): {e}",
+            cfg.anchor_date
+        )
+    })?;
+    let tz = resolve_timezone(cfg.timezone.as_deref())?;
+
+    // 锚点当天或之后的第一个目标星期几作为第 0 次
+    let offset = (cfg.weekday - i64::from(anchor.weekday().num_days_from_sunday())).rem_euclid(7);
+    let first = anchor + chrono::Duration::days(offset);
+    let period_days = cfg.every_n_weeks * 7;
+
+    let Some(from_local) = tz.timestamp_millis_opt(from_ms).single() else {
+        return Ok(None);
+    };
+    let elapsed_days = (from_local.date_naive() - first).num_days();
+    let base = if elapsed_days > 0 {
+        elapsed_days / period_days
+    } else {
+        0
+    };
+
+    // 候选日期可能已过了当天的时间点，最多再往后看一个周期
+    for k in base..base + 2 {
+        let date = first + chrono::Duration::days(k * period_days);
+        if let Some(at) = localize(tz, date.and_time(time)) {
+            if at.timestamp_millis() > from_ms {
+                return Ok(Some(at.timestamp_millis()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+// 农历触发最多向后找两年多，覆盖闰月和"三十"这类不是每月都有的日子
+const LUNAR_SEARCH_DAYS: i64 = 800;
+
+/// 农历日期触发：逐日把公历换算成农历（中国规则，2100 年前为预计算数据），找到第一个匹配的日子
+fn lunar_next_ms(cfg: &LunarTriggerConfig, from_ms: i64) -> Result<Option<i64>, String> {
+    if cfg.lunar_month.is_some_and(|m| !(1..=12).contains(&m)) {
+        return Err("lunarMonth must be 1-12".to_string());
+    }
+    if !(1..=30).contains(&cfg.lunar_day) {
+        return Err("lunarDay must be 1-30".to_string());
+    }
+    let time = NaiveTime::parse_from_str(&cfg.time, "%H:%M")
+        .map_err(|e| format!("invalid time {:?} (expected HH:MM): {e}", cfg.time))?;
+    let tz = resolve_timezone(cfg.timezone.as_deref())?;
+
+    let Some(from_local) = tz.timestamp_millis_opt(from_ms).single() else {
+        return Ok(None);
+    };
+    let calendar = ChineseTraditional::new();
+
+    let mut date = from_local.date_naive();
+    for _ in 0..LUNAR_SEARCH_DAYS {
+        let iso = IcuDate::try_new_iso(date.year(), date.month() as u8, date.day() as u8)
+            .map_err(|e| format!("failed to convert {date} to lunar calendar: {e:?}"))?;
+        let lunar = iso.to_calendar(calendar);
+        let month = lunar.month();
+        let is_leap = month.leap_status() == LeapStatus::Leap;
+
+        let month_ok = cfg.lunar_month.is_none_or(|m| m == month.number());
+        let leap_ok = !is_leap || cfg.match_leap_month;
+        if month_ok && leap_ok && lunar.day_of_month().0 == cfg.lunar_day {
+            if let Some(at) = localize(tz, date.and_time(time)) {
+                if at.timestamp_millis() > from_ms {
+                    return Ok(Some(at.timestamp_millis()));
+                }
+            }
+        }
+
+        let Some(next) = date.succ_opt() else {
+            break;
+        };
+        date = next;
+    }
+    Ok(None)
+}
+
+// 极昼/极夜期间可能连续几个月没有日出日落
+const SOLAR_SEARCH_DAYS: i64 = 370;
+// 日出/日落定义为太阳上沿在地平线上，含大气折射
+const SOLAR_HORIZON_DEGREES: f64 = -0.833;
+
+/// 日出/日落触发：在触发器时区里逐日计算，加上 offsetMinutes 后取第一个晚于 from_ms 的时间
+fn solar_next_ms(cfg: &SolarTriggerConfig, from_ms: i64) -> Result<Option<i64>, String> {
+    let rising = match cfg.event.as_str() {
+        "sunrise" => true,
+        "sunset" => false,
+        other => {
+            return Err(format!(
+                "unknown solar event: {other} (expected sunrise/sunset)"
+            ))
+        }
+    };
+    if !(-90.0..=90.0).contains(&cfg.latitude) {
+        return Err("latitude must be between -90 and 90".to_string());
+    }
+    if !(-180.0..=180.0).contains(&cfg.longitude) {
+        return Err("longitude must be between -180 and 180".to_string());
+    }
+    let tz = resolve_timezone(cfg.timezone.as_deref())?;
+
+    let Some(from_local) = tz.timestamp_millis_opt(from_ms).single() else {
+        return Ok(None);
+    };
+    // 从前一天开始，offset 为负时当天的事件可能已经落在前一天的计算结果里
+    let mut date = from_local
+        .date_naive()
+        .pred_opt()
+        .unwrap_or(from_local.date_naive());
+    for _ in 0..SOLAR_SEARCH_DAYS {
+        if let Some(at) = solar_event_ms(date, cfg.latitude, cfg.longitude, rising) {
+            let at = at + cfg.offset_minutes * 60_000;
+            if at > from_ms {
+                return Ok(Some(at));
+            }
+        }
+        let Some(next) = date.succ_opt() else {
+            break;
+        };
+        date = next;
+    }
+    Ok(None)
+}
+
+/// 日出方程（NOAA 简化版，误差在一两分钟内）；极昼/极夜当天返回 None
+fn solar_event_ms(date: NaiveDate, latitude: f64, longitude: f64, rising: bool) -> Option<i64> {
+    // J2000.0 = 2000-01-01 12:00 UTC
+    let n = (date - NaiveDate::from_ymd_opt(2000, 1, 1)?).num_days() as f64;
+    let mean_solar_time = n - longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * mean_solar_time).rem_euclid(360.0);
+    let m = anomaly.to_radians();
+    let center = 1.9148 * m.sin() + 0.02 * (2.0 * m).sin() + 0.0003 * (3.0 * m).sin();
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372)
+        .rem_euclid(360.0)
+        .to_radians();
+    let transit = 2_451_545.0 + mean_solar_time + 0.0053 * m.sin()
+        - 0.0069 * (2.0 * ecliptic_longitude).sin();
+    let declination = (ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin()).asin();
+
+    let phi = latitude.to_radians();
+    let cos_hour_angle = (SOLAR_HORIZON_DEGREES.to_radians().sin() - phi.sin() * declination.sin())
+        / (phi.cos() * declination.cos());
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees() / 360.0;
+    let julian = if rising {
+        transit - hour_angle
+    } else {
+        transit + hour_angle
+    };
+    // 儒略日 2440587.5 = 1970-01-01 00:00 UTC
+    Some(((julian - 2_440_587.5) * 86_400_000.0).round() as i64)
+}
+
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+/// 把 TS 侧的 5 段 cron（分 时 日 月 周）转换成 `cron` crate 需要的 6 段表达式；
+/// 也接受显式带秒字段的 6 段写法（秒 分 时 日 月 周），其余字段按同样规则转换。
+///
+/// 支持的语法子集：`*`、数字、`a-b` 范围、`/n` 步长、`,` 列表；月份可用 `JAN`..`DEC`，
+/// 星期可用 `SUN`..`SAT`（大小写均可）。星期字段的数字按标准 crontab 语义解释
+/// （0 和 7 都是周日，1-5 为周一到周五），这里统一改写成英文缩写，因为 `cron` crate
+/// 的数字星期是 1=周日，直接透传会整体错位一天。不支持 `L`、`W`、`#`、`?`。
+fn normalize_cron_expression(expr: &str) -> Result<String, String> {
+    let all_fields: Vec<&str> = expr.split_whitespace().collect();
+    let (second, fields) = match all_fields.len() {
+        5 => ("0", all_fields.as_slice()),
+        6 => (all_fields[0], &all_fields[1..]),
+        n => {
+            return Err(format!(
+                "cron expression must have 5 or 6 fields ([second] minute hour day month weekday), got {n}"
+            ))
+        }
+    };
+
+    let weekday = fields[4]
+        .split(',')
+        .map(normalize_weekday_item)
+        .collect::<Result<Vec<_>, _>>()?
+        .join(",");
+
+    Ok(format!(
+        "{second} {} {} {} {} {}",
+        fields[0],
+        fields[1],
+        fields[2],
+        fields[3].to_ascii_uppercase(),
+        weekday
+    ))
+}
+
+fn normalize_weekday_item(item: &str) -> Result<String, String> {
+    let item = item.to_ascii_uppercase();
+    let (base, step) = match item.split_once('/') {
+        Some((base, step)) => (base, Some(step)),
+        None => (item.as_str(), None),
+    };
+
+    let base = match base.split_once('-') {
+        Some((start, end)) => {
+            let start = weekday_token(start)?;
+            let end = weekday_token(end)?;
+            // 7 表示周日，`cron` crate 不支持回绕范围，拆成 "x-SAT,SUN"
+            if end == "SUN" && start != "SUN" {
+                if step.is_some() {
+                    return Err(format!("unsupported weekday range with step: {item}"));
+                }
+                return Ok(format!("{start}-SAT,SUN"));
+            }
+            format!("{start}-{end}")
+        }
+        None if base == "*" => base.to_string(),
+        None => weekday_token(base)?.to_string(),
+    };
+
+    Ok(match step {
+        Some(step) => format!("{base}/{step}"),
+        None => base,
+    })
+}
+
+fn weekday_token(token: &str) -> Result<&'static str, String> {
+    if let Ok(n) = token.parse::<usize>() {
+        return match n {
+            0..=6 => Ok(WEEKDAY_NAMES[n]),
+            7 => Ok("SUN"),
+            _ => Err(format!("weekday out of range (0-7): {token}")),
+        };
+    }
+    WEEKDAY_NAMES
+        .iter()
+        .find(|name| name.eq_ignore_ascii_case(token))
+        .copied()
+        .ok_or_else(|| format!("unknown weekday: {token}"))
+}
+
+/// 系统本地时区；解析不到 IANA 名称时退回 UTC
+fn system_timezone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// 触发器上的 `timezone` 字段（IANA 名称），缺省时按系统本地时区解释
+fn resolve_timezone(name: Option<&str>) -> Result<Tz, String> {
+    match name.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(name) => name
+            .parse::<Tz>()
+            .map_err(|_| format!("unknown timezone: {name}")),
+        None => Ok(system_timezone()),
+    }
+}
+
+fn parse_cron_schedule(expr: &str) -> Result<Schedule, String> {
+    let expr_6 = normalize_cron_expression(expr)?;
+    Schedule::from_str(&expr_6).map_err(|e| format!("invalid cron expression {expr:?}: {e}"))
+}
+
+fn cron_next_ms(schedule: &Schedule, tz: Tz, from_ms: i64) -> Option<i64> {
+    let from_dt: DateTime<Tz> = tz.timestamp_millis_opt(from_ms).single()?;
+    schedule
+        .after(&from_dt)
+        .next()
+        .map(|dt| dt.timestamp_millis())
+}
+
+/// metadata 中调度器会读取的字段；其余字段留给前端自由使用
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct TaskOptions {
+    #[serde(alias = "warn_if_slower_than_ms")]
+    warn_if_slower_than_ms: Option<i64>,
+    /// 上一次还在执行时又到期："skip" | "queue" | "parallel"（缺省）
+    #[serde(alias = "overlap_policy")]
+    overlap_policy: Option<String>,
+    #[serde(alias = "skip_rules")]
+    skip_rules: Option<SkipRules>,
+    /// 开启 pauseOnBattery 时，标记了的任务在电池供电下照常执行
+    #[serde(alias = "run_on_battery")]
+    run_on_battery: bool,
+    /// 到期时先发 task_awaiting_confirmation，等 scheduler_confirm_execution 批准后才执行
+    #[serde(alias = "requires_confirmation")]
+    requires_confirmation: bool,
+    /// 错过触发（到期超过 MISFIRE_GRACE_MS）时："skip" | "run_once"（缺省）| "run_all"
+    #[serde(alias = "misfire_policy")]
+    misfire_policy: Option<String>,
+}
+
+// skipRules 最多往后跳多少次（约一年的逐日跳过），防止规则排除了所有日期时死循环
+const SKIP_RULES_MAX_STEPS: usize = 400;
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SkipRules {
+    skip_weekends: bool,
+    /// "YYYY-MM-DD"，按任务时区的本地日期比较
+    skip_dates: Vec<String>,
+}
+
+impl SkipRules {
+    fn skips(&self, date: NaiveDate) -> bool {
+        if self.skip_weekends
+            && matches!(date.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun)
+        {
+            return true;
+        }
+        let day = date.format("%Y-%m-%d").to_string();
+        self.skip_dates.iter().any(|d| d.trim() == day)
+    }
+}
+
+fn task_options(metadata: Option<&str>) -> TaskOptions {
+    metadata
+        .and_then(|m| serde_json::from_str::<TaskOptions>(m).ok())
+        .unwrap_or_default()
+}
+
+/// 由前端真正执行、跑完后通过 scheduler_complete_execution 回报结果的 action 类型
+fn is_async_action(action_type: &str) -> bool {
+    matches!(action_type, "agent_task" | "workflow")
+}
+
+/// 执行耗时超过任务设定的阈值时标记执行记录并通知前端
+fn check_slow_execution(
+    app: &AppHandle,
+    conn: &Connection,
+    task: &DbTaskRow,
+    exec_id: &str,
+    duration: i64,
+) -> Result<(), String> {
+    let Some(threshold) = task_options(task.metadata.as_deref()).warn_if_slower_than_ms else {
+        return Ok(());
+    };
+    if duration <= threshold {
+        return Ok(());
+    }
+
+    conn.execute(
+        "UPDATE task_executions SET slow = 1 WHERE id = ?",
+        params![exec_id],
+    )
+    .map_err(|e| format!("failed to flag slow execution: {e}"))?;
+    let _ = app.emit(
+        "task_slow",
+        serde_json::json!({
+            "taskId": task.id,
+            "executionId": exec_id,
+            "durationMs": duration,
+            "thresholdMs": threshold,
+        }),
+    );
+    Ok(())
+}
+
+fn execute_task(app: &AppHandle, conn: &Connection, task: &DbTaskRow) -> Result<(), String> {
+    let start_ms = now_ms();
+
+    let exec_id = Uuid::new_v4().to_string();
+    conn.execute(
+        r#"
+INSERT INTO task_executions (id, task_id, status, started_at)
+VALUES (?, ?, 'running', ?)
+"#,
+        params![exec_id, task.id, start_ms],
+    )
+    .map_err(|e| format!("failed to insert execution: {e}"))?;
+
+    run_execution(app, conn, task, exec_id, start_ms)
+}
+
+/// action 类型被全局禁用：不执行，执行记录标为 blocked，照常推进 next_run
+fn block_execution(
+    app: &AppHandle,
+    conn: &Connection,
+    task: &DbTaskRow,
+    exec_id: &str,
+    start_ms: i64,
+    blocked_type: &str,
+) -> Result<(), String> {
+    conn.execute(
+        r#"
+UPDATE task_executions
+SET status = 'blocked', completed_at = ?, error = ?, duration = 0
+WHERE id = ?
+"#,
+        params![
+            start_ms,
+            format!("action type {blocked_type} is disabled"),
+            exec_id
+        ],
+    )
+    .map_err(|e| format!("failed to update execution: {e}"))?;
+
+    let next_run = compute_next_run(
+        &task.trigger_type,
+        &task.trigger_config,
+        task.metadata.as_deref(),
+        start_ms,
+    );
+    conn.execute(
+        "UPDATE tasks SET next_run = ?, updated_at = ? WHERE id = ?",
+        params![next_run, start_ms, task.id],
+    )
+    .map_err(|e| format!("failed to advance blocked task: {e}"))?;
+
+    let _ = app.emit(
+        "task_blocked",
+        serde_json::json!({
+            "taskId": task.id,
+            "executionId": exec_id,
+            "actionType": blocked_type,
+        }),
+    );
+    Ok(())
+}
+
+/// 推给订阅了该任务的 channel：`{ event, payload }`，payload 与同名全局事件一致；
+/// 发送失败（前端已销毁）的 channel 直接移除
+fn notify_task_subscribers(
+    app: &AppHandle,
+    task_id: &str,
+    event: &str,
+    payload: serde_json::Value,
+) {
+    let state = runtime_state(app);
+    let mut subscribers = state
+        .task_subscribers
+        .lock()
+        .unwrap_or_else(|p| p.into_inner());
+    let Some(channels) = subscribers.get_mut(task_id) else {
+        return;
+    };
+    let message = serde_json::json!({ "event": event, "payload": payload });
+    channels.retain(|channel| channel.send(message.clone()).is_ok());
+    if channels.is_empty() {
+        subscribers.remove(task_id);
+    }
+}
+
+/// 一次 action 的执行结果；status 为 success / suppressed / throttled / failed
+struct ActionOutcome {
+    status: String,
+    result_json: Option<String>,
+    error: Option<String>,
+}
+
+/// 只负责 action 本身的副作用（发事件给前端），不读写数据库；dnd 为 true 时通知只记录不推送，
+/// throttle 为 true 时通知受全局令牌桶限制（用户主动触发的测试/重放不受限）。
+/// exec_id 随异步 action 的事件一起发出，前端跑完后用它调 scheduler_complete_execution
+fn perform_action(
+    app: &AppHandle,
+    action_type: &str,
+    action_config: &str,
+    exec_id: Option<&str>,
+    dnd: bool,
+    throttle: bool,
+) -> ActionOutcome {
+    let mut status = "success".to_string();
+    let mut result_json: Option<String> = None;
+    let mut error: Option<String> = None;
+
+    match action_type {
+        "notification" => {
+            match serde_json::from_str::<NotificationActionConfig>(action_config) {
+                Ok(cfg) => {
+                    let channel = cfg.channel.as_deref().unwrap_or("os");
+                    let payload = serde_json::json!({
+                        "title": cfg.title,
+                        "body": cfg.body,
+                        "actionButton": cfg.action_button,
+                        "actionCallback": cfg.action_callback,
+                        "channel": channel,
+                    });
+                    let (to_os, to_in_app) = match channel {
+                        "os" => (true, false),
+                        "in_app" => (false, true),
+                        "both" => (true, true),
+                        other => {
+                            status = "failed".to_string();
+                            error = Some(format!("unknown notification channel: {other}"));
+                            (false, false)
+                        }
+                    };
+                    if status == "success" {
+                        // 勿扰模式：照常记录执行，但不把通知推给用户
+                        if dnd {
+                            status = "suppressed".to_string();
+                        } else if throttle && !take_notification_token(app, now_ms()) {
+                            status = "throttled".to_string();
+                        } else {
+                            if to_os {
+                                let _ = app.emit("task_notification", payload.clone());
+                            }
+                            if to_in_app {
+                                let _ = app.emit("task_in_app_toast", payload.clone());
+                            }
+                        }
+                    }
+                    result_json = Some(payload.to_string());
+                }
+                Err(e) => {
+                    status = "failed".to_string();
+                    error = Some(format!("invalid notification action config: {e}"));
+                }
+            }
+        }
+        "agent_task" => match serde_json::from_str::<AgentTaskActionConfig>(action_config) {
+            Ok(cfg) => {
+                let payload = serde_json::json!({
+                    "prompt": cfg.prompt,
+                    "toolsAllowed": cfg.tools_allowed,
+                    "maxSteps": cfg.max_steps,
+                    "context": cfg.context,
+                    "model": cfg.model,
+                    "executionId": exec_id,
+                });
+                let _ = app.emit("task_agent_execute", payload.clone());
+                result_json = Some(payload.to_string());
+            }
+            Err(e) => {
+                status = "failed".to_string();
+                error = Some(format!("invalid agent_task action config: {e}"));
+            }
+        },
+        "workflow" => match serde_json::from_str::<WorkflowActionConfig>(action_config) {
+            Ok(cfg) => {
+                let payload = serde_json::json!({
+                    "workflowId": cfg.workflow_id,
+                    "input": cfg.input,
+                    "executionId": exec_id,
+                });
+                let _ = app.emit("task_workflow_execute", payload.clone());
+                result_json = Some(payload.to_string());
+            }
+            Err(e) => {
+                status = "failed".to_string();
+                error = Some(format!("invalid workflow action config: {e}"));
+            }
+        },
+        "pet_reaction" => match serde_json::from_str::<PetReactionActionConfig>(action_config) {
+            Ok(cfg) => {
+                if !PET_REACTION_ANIMATIONS.contains(&cfg.animation.as_str()) {
+                    status = "failed".to_string();
+                    error = Some(format!(
+                        "unknown pet animation: {} (expected one of: {})",
+                        cfg.animation,
+                        PET_REACTION_ANIMATIONS.join(", ")
+                    ));
+                } else if cfg.duration_ms.is_some_and(|ms| ms <= 0) {
+                    status = "failed".to_string();
+                    error = Some("pet_reaction durationMs must be > 0".to_string());
+                } else {
+                    let payload = serde_json::json!({
+                        "animation": cfg.animation,
+                        "sound": cfg.sound,
+                        "durationMs": cfg.duration_ms,
+                    });
+                    let _ = app.emit("task_pet_reaction", payload.clone());
+                    result_json = Some(payload.to_string());
+                }
+            }
+            Err(e) => {
+                status = "failed".to_string();
+                error = Some(format!("invalid pet_reaction action config: {e}"));
+            }
+        },
+        "script" => {
+            status = "failed".to_string();
+            error = Some("script action is not supported yet".to_string());
+        }
+        "task_control" => match serde_json::from_str::<TaskControlActionConfig>(action_config) {
+            Ok(cfg) => match perform_task_control(app, &cfg) {
+                Ok(result) => result_json = Some(result.to_string()),
+                Err(e) => {
+                    status = "failed".to_string();
+                    error = Some(e);
+                }
+            },
+            Err(e) => {
+                status = "failed".to_string();
+                error = Some(format!("invalid task_control action config: {e}"));
+            }
+        },
+        "sequence" => match serde_json::from_str::<SequenceActionConfig>(action_config) {
+            Ok(cfg) => {
+                let (ok, steps, first_error) = perform_sequence(app, &cfg, dnd, throttle);
+                if !ok {
+                    status = "failed".to_string();
+                    error = first_error;
+                }
+                result_json = Some(serde_json::json!({ "steps": steps }).to_string());
+            }
+            Err(e) => {
+                status = "failed".to_string();
+                error = Some(format!("invalid sequence action config: {e}"));
+            }
+        },
+        other => {
+            status = "failed".to_string();
+            error = Some(format!("unknown action type: {other}"));
+        }
+    }
+
+    ActionOutcome {
+        status,
+        result_json,
+        error,
+    }
+}
+
+/// task_control 的具体操作，复用对应命令的实现。run_now 拒绝正在执行中的目标：
+/// 任务让自己立即执行、或 A→B→A 这样的互相触发都会在这里被拦下，不会无限递归
+fn perform_task_control(
+    app: &AppHandle,
+    cfg: &TaskControlActionConfig,
+) -> Result<serde_json::Value, String> {
+    let target = cfg.target_task_id.as_str();
+    {
+        let conn = open_db(app)?;
+        ensure_tables(&conn)?;
+        if get_db_task(&conn, target)?.is_none() {
+            return Err(format!("target task not found: {target}"));
+        }
+    }
+
+    match cfg.operation.as_str() {
+        "enable" => scheduler_enable_task(app.clone(), target.to_string(), true)?,
+        "disable" => scheduler_enable_task(app.clone(), target.to_string(), false)?,
+        "run_now" => {
+            if is_in_flight(target) {
+                return Err(format!(
+                    "target task {target} is already running, refusing to run it again"
+                ));
+            }
+            scheduler_execute_now(app.clone(), target.to_string())?;
+        }
+        "snooze" => {
+            let minutes = cfg
+                .arg
+                .filter(|m| *m > 0)
+                .ok_or_else(|| "snooze requires arg > 0 (minutes)".to_string())?;
+            scheduler_set_next_run(app.clone(), target.to_string(), now_ms() + minutes * 60_000)?;
+        }
+        other => {
+            return Err(format!(
+                "unknown task_control operation: {other} (expected enable/disable/run_now/snooze)"
+            ))
+        }
+    }
+
+    Ok(serde_json::json!({
+        "targetTaskId": target,
+        "operation": cfg.operation,
+        "arg": cfg.arg,
+    }))
+}
+
+/// 依次执行 sequence 的每一步，返回 (必需步骤是否全部成功, 每一步的结果, 第一个导致失败的错误)。
+/// 不允许嵌套 sequence
+fn perform_sequence(
+    app: &AppHandle,
+    cfg: &SequenceActionConfig,
+    dnd: bool,
+    throttle: bool,
+) -> (bool, Vec<serde_json::Value>, Option<String>) {
+    let mut steps = Vec::with_capacity(cfg.steps.len());
+    for (index, step) in cfg.steps.iter().enumerate() {
+        let action_type = step.action.type_name();
+        let outcome = match step.action.to_db() {
+            Ok(_) if action_type == "sequence" => ActionOutcome {
+                status: "failed".to_string(),
+                result_json: None,
+                error: Some("nested sequence actions are not supported".to_string()),
+            },
+            Ok((action_type, action_config)) => {
+                perform_action(app, &action_type, &action_config, None, dnd, throttle)
+            }
+            Err(e) => ActionOutcome {
+                status: "failed".to_string(),
+                result_json: None,
+                error: Some(e),
+            },
+        };
+        let result = outcome
+            .result_json
+            .as_deref()
+            .and_then(|r| serde_json::from_str::<serde_json::Value>(r).ok());
+        steps.push(serde_json::json!({
+            "index": index,
+            "type": action_type,
+            "status": outcome.status,
+            "result": result,
+            "error": outcome.error,
+        }));
+        if outcome.status == "failed" && !step.continue_on_error {
+            let error = format!(
+                "step {index} ({action_type}) failed: {}",
+                outcome.error.unwrap_or_else(|| "unknown error".to_string())
+            );
+            return (false, steps, Some(error));
+        }
+    }
+    (true, steps, None)
+}
+
+/// 执行时会用到的所有 action 类型；sequence 展开成各步骤的类型，供全局禁用开关检查
+fn effective_action_types(action_type: &str, action_config: &str) -> Vec<String> {
+    let mut types = vec![action_type.to_string()];
+    if action_type == "sequence" {
+        if let Ok(cfg) = serde_json::from_str::<SequenceActionConfig>(action_config) {
+            types.extend(
+                cfg.steps
+                    .iter()
+                    .map(|step| step.action.type_name().to_string()),
+            );
+        }
+    }
+    types
+}
+
+fn load_consecutive_failures(conn: &Connection, task_id: &str) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT consecutive_failures FROM tasks WHERE id = ?",
+        params![task_id],
+        |r| r.get(0),
+    )
+    .map_err(|e| format!("failed to read consecutive failures: {e}"))
+}
+
+/// 连续失败的汇总事件，同一任务至多每 FAILURE_COALESCE_EMIT_INTERVAL_MS 发一次
+fn emit_failing_repeatedly(
+    app: &AppHandle,
+    task_id: &str,
+    exec_id: &str,
+    failure_count: i64,
+    error: &str,
+    now_ms: i64,
+) {
+    if !runtime_state(app).claim_failing_notification(task_id, now_ms) {
+        return;
+    }
+
+    log::warn!("[Scheduler] task {task_id} failed {failure_count} times in a row: {error}");
+    let _ = app.emit(
+        "task_failing_repeatedly",
+        serde_json::json!({
+            "taskId": task_id,
+            "executionId": exec_id,
+            "failureCount": failure_count,
+            "lastError": error,
+        }),
+    );
+}
+
+/// 执行时的 action 配置快照，任务之后被修改也能按原样重放
+fn action_snapshot(action_type: &str, action_config: &str) -> String {
+    serde_json::json!({ "actionType": action_type, "actionConfig": action_config }).to_string()
+}
+
+/// 对一条已处于 running 的执行记录跑 action，写回结果并重排 next_run
+fn run_execution(
+    app: &AppHandle,
+    conn: &Connection,
+    task: &DbTaskRow,
+    exec_id: String,
+    start_ms: i64,
+) -> Result<(), String> {
+    let disabled = load_disabled_action_types(conn)?;
+    if let Some(blocked) = effective_action_types(&task.action_type, &task.action_config)
+        .into_iter()
+        .find(|t| disabled.contains(t))
+    {
+        return block_execution(app, conn, task, &exec_id, start_ms, &blocked);
+    }
+
+    let _in_flight = InFlightGuard::enter(&task.id);
+    let _ = app.emit("task_started", task.id.clone());
+    notify_task_subscribers(
+        app,
+        &task.id,
+        "task_started",
+        serde_json::json!({ "taskId": task.id, "executionId": exec_id }),
+    );
+
+    let dnd = matches!(task.action_type.as_str(), "notification" | "sequence")
+        && load_dnd(conn, start_ms).is_ok_and(|d| d.enabled);
+    // 只计 action 的分发部分（配置解析 + emit）；异步 action 的真实耗时由前端跑完后
+    // 调 scheduler_complete_execution 回报，写入 duration
+    let dispatch_started = Instant::now();
+    let ActionOutcome {
+        status,
+        result_json,
+        error,
+    } = perform_action(
+        app,
+        &task.action_type,
+        &task.action_config,
+        Some(&exec_id),
+        dnd,
+        true,
+    );
+    let dispatch_ms = dispatch_started.elapsed().as_millis() as i64;
+
+    let end_ms = now_ms();
+    let duration = end_ms.saturating_sub(start_ms);
+
+    let (stored_result, result_path) = match result_json.as_deref() {
+        Some(full) => {
+            let max_bytes = load_max_result_bytes(conn)?;
+            let (stored, path) = spill_result(app, &exec_id, full, max_bytes)?;
+            (Some(stored), path)
+        }
+        None => (None, None),
+    };
+
+    conn.execute(
+        r#"
+UPDATE task_executions
+SET status = ?, completed_at = ?, result = ?, error = ?, duration = ?, result_path = ?,
+    config_snapshot = ?, dispatch_ms = ?
+WHERE id = ?
+"#,
+        params![
+            status,
+            end_ms,
+            stored_result,
+            error,
+            duration,
+            result_path,
+            action_snapshot(&task.action_type, &task.action_config),
+            dispatch_ms,
+            exec_id
+        ],
+    )
+    .map_err(|e| format!("failed to update execution: {e}"))?;
+
+    // 异步 action 此时只是发出了事件，真实耗时等 scheduler_complete_execution 回报后再检查
+    if !is_async_action(&task.action_type) {
+        check_slow_execution(app, conn, task, &exec_id, duration)?;
+    }
+
+    let failed = !matches!(status.as_str(), "success" | "suppressed" | "throttled");
+    let consecutive_failures = if !failed {
+        0
+    } else if task
+        .last_run
+        .is_some_and(|last| end_ms - last <= FAILURE_COALESCE_WINDOW_MS)
+    {
+        load_consecutive_failures(conn, &task.id)? + 1
+    } else {
+        1
+    };
+
+    // 更新任务的 last_run/next_run
+    let next_run = compute_next_run(
+        &task.trigger_type,
+        &task.trigger_config,
+        task.metadata.as_deref(),
+        end_ms,
+    );
+    conn.execute(
+        r#"
+UPDATE tasks
+SET last_run = ?, next_run = ?, updated_at = ?, consecutive_failures = ?
+WHERE id = ?
+"#,
+        params![end_ms, next_run, end_ms, consecutive_failures, task.id],
+    )
+    .map_err(|e| format!("failed to update task run info: {e}"))?;
+
+    // scheduler_disable_after_next_run 标记过的任务，跑完这一次就停用
+    let auto_disabled = conn
+        .execute(
+            r#"
+UPDATE tasks
+SET enabled = 0, next_run = NULL, disable_after_run = 0, updated_at = ?
+WHERE id = ? AND disable_after_run = 1
+"#,
+            params![end_ms, task.id],
+        )
+        .map_err(|e| format!("failed to auto-disable task: {e}"))?
+        > 0;
+    if auto_disabled {
+        record_audit(
+            conn,
+            &task.id,
+            enabled_audit_event(false),
+            Some("disable_after_run"),
+        )?;
+        let _ = app.emit(
+            "task_auto_disabled",
+            serde_json::json!({ "taskId": task.id, "executionId": exec_id }),
+        );
+    }
+
+    // 事件负载（前端 TaskCompletedEvent / TaskFailedEvent）：
+    // task_completed 以前只发 task id 字符串，现在与 task_failed 一样发对象；
+    // task_failed 保留旧的 `id` 字段，兼容还没迁移到 `taskId` 的监听方
+    match status.as_str() {
+        "success" | "suppressed" | "throttled" => {
+            let result = result_json
+                .as_deref()
+                .and_then(|r| serde_json::from_str::<serde_json::Value>(r).ok());
+            let payload = serde_json::json!({
+                "taskId": task.id,
+                "executionId": exec_id,
+                "status": status,
+                "durationMs": duration,
+                "result": result,
+            });
+            let _ = app.emit("task_completed", payload.clone());
+            notify_task_subscribers(app, &task.id, "task_completed", payload);
+            runtime_state(app).clear_failing_notification(&task.id);
+        }
+        _ => {
+            let error = error.unwrap_or_else(|| "unknown error".to_string());
+            let payload = serde_json::json!({
+                "id": task.id,
+                "taskId": task.id,
+                "executionId": exec_id,
+                "durationMs": duration,
+                "error": error,
+            });
+            // 单个坏任务反复失败时只发节流后的汇总事件；订阅了该任务的 channel 仍收到每一次失败
+            if consecutive_failures >= FAILURE_COALESCE_THRESHOLD {
+                emit_failing_repeatedly(
+                    app,
+                    &task.id,
+                    &exec_id,
+                    consecutive_failures,
+                    &error,
+                    end_ms,
+                );
+            } else {
+                let _ = app.emit("task_failed", payload.clone());
+            }
+            notify_task_subscribers(app, &task.id, "task_failed", payload);
+        }
+    }
+
+    Ok(())
+}
+
+// ====== Tauri commands ======
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTrigger {
+    #[serde(rename = "type")]
+    pub r#type: String,
+    pub config: TriggerConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAction {
+    #[serde(rename = "type")]
+    pub r#type: String,
+    pub config: ActionConfig,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTask {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub trigger: ApiTrigger,
+    pub action: ApiAction,
+    pub enabled: bool,
+    pub last_run: Option<i64>,
+    pub next_run: Option<i64>,
+    pub metadata: Option<serde_json::Value>,
+    pub created_at: i64,
+    pub updated_at: Option<i64>,
+    /// 同时到期时先执行优先级高的
+    pub priority: i64,
+    /// 启用的任务等于 next_run；停用的任务为"现在启用的话"的下一次时间，仅供展示，不落库。
+    /// 停用任务只有 scheduler_get_task / scheduler_get_all_tasks 会填，其余接口为空
+    #[serde(default, skip_deserializing)]
+    pub preview_next_run: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiTaskExecution {
+    pub id: String,
+    pub task_id: String,
+    pub status: String,
+    pub started_at: i64,
+    pub completed_at: Option<i64>,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub duration: Option<i64>,
+    /// 耗时超过 metadata.warnIfSlowerThanMs
+    pub slow: bool,
+    /// result 被截断时完整结果所在的文件，用 scheduler_get_execution_result 读取
+    pub result_path: Option<String>,
+    /// action 分发（配置解析 + emit）耗时，旧记录为空
+    pub dispatch_ms: Option<i64>,
+}
+
+const EXECUTION_COLUMNS: &str = "id, task_id, status, started_at, completed_at, result, error, \
+     duration, slow, result_path, dispatch_ms";
+
+fn execution_from_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<ApiTaskExecution> {
+    Ok(ApiTaskExecution {
+        id: r.get(0)?,
+        task_id: r.get(1)?,
+        status: r.get(2)?,
+        started_at: r.get(3)?,
+        completed_at: r.get(4)?,
+        result: r.get(5)?,
+        error: r.get(6)?,
+        duration: r.get(7)?,
+        slow: r.get::<_, Option<i64>>(8)?.unwrap_or(0) == 1,
+        result_path: r.get(9)?,
+        dispatch_ms: r.get(10)?,
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IntervalTriggerConfig {
+    seconds: i64,
+    /// "elapsed"（缺省，按真实流逝时间）| "wallclock"（按本地日历时间，跨夏令时不漂移）
+    #[serde(default)]
+    interval_mode: Option<String>,
+    /// 仅 wallclock 模式使用
+    #[serde(default)]
+    timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CronTriggerConfig {
+    expression: String,
+    #[serde(default)]
+    timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WeeklyTriggerConfig {
+    every_n_weeks: i64,
+    /// 0 = 周日 … 6 = 周六（与 JS `Date#getDay` 一致）
+    weekday: i64,
+    /// 本地墙上时间 "HH:MM"，跨夏令时保持不变
+    time: String,
+    /// "YYYY-MM-DD"，决定"隔 N 周"从哪一周开始数
+    anchor_date: String,
+    #[serde(default)]
+    timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SolarTriggerConfig {
+    /// "sunrise" | "sunset"
+    event: String,
+    latitude: f64,
+    longitude: f64,
+    /// 相对日出/日落的偏移，负数表示提前
+    #[serde(default)]
+    offset_minutes: i64,
+    /// 决定按哪一天计算；缺省为系统时区
+    #[serde(default)]
+    timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LunarTriggerConfig {
+    /// 农历月份 1-12；不填表示每个农历月（例如每月初一、十五）
+    #[serde(default)]
+    lunar_month: Option<u8>,
+    /// 农历日 1-30；小月没有三十时该月跳过
+    lunar_day: u8,
+    /// 本地墙上时间 "HH:MM"
+    time: String,
+    /// 闰月是否也触发；默认跳过闰月，只在正月份触发
+    #[serde(default)]
+    match_leap_month: bool,
+    #[serde(default)]
+    timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IdleTriggerConfig {
+    idle_seconds: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationActionConfig {
+    title: String,
+    body: String,
+    #[serde(default)]
+    action_button: Option<String>,
+    #[serde(default)]
+    action_callback: Option<String>,
+    /// "os" | "in_app" | "both"，缺省为 "os"
+    #[serde(default)]
+    channel: Option<String>,
+}
+
+// 与前端 src/config/interaction-effects.ts 中宠物动画名保持一致
+const PET_REACTION_ANIMATIONS: [&str; 8] = [
+    "idle", "happy", "eat", "tap_head", "clean", "sleep", "work", "study",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PetReactionActionConfig {
+    animation: String,
+    #[serde(default)]
+    sound: Option<String>,
+    #[serde(default)]
+    duration_ms: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentTaskActionConfig {
+    prompt: String,
+    #[serde(default)]
+    tools_allowed: Option<Vec<String>>,
+    #[serde(default)]
+    max_steps: Option<i64>,
+    /// 原样转发给前端 agent runner（会话 id、变量等）
+    #[serde(default)]
+    context: Option<serde_json::Value>,
+    #[serde(default)]
+    model: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowActionConfig {
+    workflow_id: String,
+    #[serde(default)]
+    input: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EventTriggerConfig {
+    event_name: String,
+    #[serde(default)]
+    filter: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompositeOp {
+    And,
+    Or,
+}
+
+/// or：取子触发器里最早的下一次时间；and：取最晚的，到点时再检查 idle/event 等条件是否都满足
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompositeTriggerConfig {
+    op: CompositeOp,
+    children: Vec<TriggerConfig>,
+}
+
+// 组合触发器最多嵌套的层数
+const MAX_COMPOSITE_DEPTH: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptActionConfig {
+    code: String,
+}
+
+/// 操作另一个任务：enable / disable / run_now / snooze（arg 为推迟的分钟数）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskControlActionConfig {
+    target_task_id: String,
+    operation: String,
+    #[serde(default)]
+    arg: Option<i64>,
+}
+
+/// 按顺序执行的一组 action；某一步失败即停止，除非该步标了 continueOnError
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SequenceActionConfig {
+    steps: Vec<SequenceStep>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SequenceStep {
+    action: ActionConfig,
+    /// 失败后继续执行后面的步骤，且不影响整体状态
+    #[serde(default)]
+    continue_on_error: bool,
+}
+
+/// 命令层的触发器配置：按 `type` 区分，与前端的结构化对象一一对应；落库时仍序列化为 trigger_config 文本
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerConfig {
+    Interval(IntervalTriggerConfig),
+    Cron(CronTriggerConfig),
+    Weekly(WeeklyTriggerConfig),
+    Lunar(LunarTriggerConfig),
+    Solar(SolarTriggerConfig),
+    Idle(IdleTriggerConfig),
+    Event(EventTriggerConfig),
+    Manual,
+    Composite(CompositeTriggerConfig),
+    /// 库里已有的配置解析失败时原样带出，避免一条坏数据让整个列表报错；不能作为输入
+    #[serde(skip_deserializing)]
+    Invalid {
+        raw: String,
+        error: String,
+    },
+}
+
+impl TriggerConfig {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Interval(_) => "interval",
+            Self::Cron(_) => "cron",
+            Self::Weekly(_) => "weekly",
+            Self::Lunar(_) => "lunar",
+            Self::Solar(_) => "solar",
+            Self::Idle(_) => "idle",
+            Self::Event(_) => "event",
+            Self::Manual => "manual",
+            Self::Composite(_) => "composite",
+            Self::Invalid { .. } => "invalid",
+        }
+    }
+
+    fn from_db(trigger_type: &str, raw: &str) -> Self {
+        parse_stored_config(trigger_type, raw).unwrap_or_else(|error| Self::Invalid {
+            raw: raw.to_string(),
+            error,
+        })
+    }
+
+    /// 拆成 (trigger_type, trigger_config) 两列
+    fn to_db(&self) -> Result<(String, String), String> {
+        if let Self::Invalid { error, .. } = self {
+            return Err(format!("invalid trigger config: {error}"));
+        }
+        let raw = serde_json::to_string(self)
+            .map_err(|e| format!("failed to encode trigger config: {e}"))?;
+        Ok((self.type_name().to_string(), raw))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ActionConfig {
+    Notification(NotificationActionConfig),
+    AgentTask(AgentTaskActionConfig),
+    Workflow(WorkflowActionConfig),
+    PetReaction(PetReactionActionConfig),
+    Script(ScriptActionConfig),
+    Sequence(SequenceActionConfig),
+    TaskControl(TaskControlActionConfig),
+    #[serde(skip_deserializing)]
+    Invalid {
+        raw: String,
+        error: String,
+    },
+}
+
+impl ActionConfig {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Notification(_) => "notification",
+            Self::AgentTask(_) => "agent_task",
+            Self::Workflow(_) => "workflow",
+            Self::PetReaction(_) => "pet_reaction",
+            Self::Script(_) => "script",
+            Self::Sequence(_) => "sequence",
+            Self::TaskControl(_) => "task_control",
+            Self::Invalid { .. } => "invalid",
+        }
+    }
+
+    fn from_db(action_type: &str, raw: &str) -> Self {
+        parse_stored_config(action_type, raw).unwrap_or_else(|error| Self::Invalid {
+            raw: raw.to_string(),
+            error,
+        })
+    }
+
+    /// 拆成 (action_type, action_config) 两列
+    fn to_db(&self) -> Result<(String, String), String> {
+        if let Self::Invalid { error, .. } = self {
+            return Err(format!("invalid action config: {error}"));
+        }
+        let raw = serde_json::to_string(self)
+            .map_err(|e| format!("failed to encode action config: {e}"))?;
+        Ok((self.type_name().to_string(), raw))
+    }
+}
+
+/// 旧数据的 config 里可能没有 `type`，用列上的类型补上再解析
+fn parse_stored_config<T: serde::de::DeserializeOwned>(
+    type_name: &str,
+    raw: &str,
+) -> Result<T, String> {
+    let mut value: serde_json::Value = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.entry("type")
+            .or_insert_with(|| serde_json::Value::String(type_name.to_string()));
+    }
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// 把前端手里的配置文本解析成对应的结构再序列化回去，得到与落库时一致的规范形式
+/// （字段顺序固定、补上 `type`、去掉未知字段），便于比较和去重。kind 为 trigger 或 action
+#[tauri::command]
+pub fn scheduler_normalize_config(
+    kind: String,
+    type_name: String,
+    config: String,
+) -> Result<String, String> {
+    let (normalized_type, raw) = match kind.as_str() {
+        "trigger" => parse_stored_config::<TriggerConfig>(&type_name, &config)
+            .map_err(|e| format!("invalid trigger config: {e}"))?
+            .to_db()?,
+        "action" => parse_stored_config::<ActionConfig>(&type_name, &config)
+            .map_err(|e| format!("invalid action config: {e}"))?
+            .to_db()?,
+        other => {
+            return Err(format!(
+                "unknown config kind: {other} (expected trigger/action)"
+            ))
+        }
+    };
+    if normalized_type != type_name {
+        return Err(format!(
+            "config type {normalized_type} does not match {type_name}"
+        ));
+    }
+    Ok(raw)
+}
+
+/// priority 越大越先执行（同一轮到期的任务超过单轮上限时尤其重要），缺省为 0
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn scheduler_create_task(
+    app: AppHandle,
+    name: String,
+    description: Option<String>,
+    trigger: TriggerConfig,
+    action: ActionConfig,
+    enabled: bool,
+    metadata: Option<String>,
+    priority: Option<i64>,
+) -> Result<String, String> {
+    let (trigger_type, trigger_config) = trigger.to_db()?;
+    let (action_type, action_config) = action.to_db()?;
+    validate_trigger_config(&trigger_type, &trigger_config)?;
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    if enabled {
+        check_enabled_task_limit(&conn)?;
+    }
+
+    let now = now_ms();
+    let id = Uuid::new_v4().to_string();
+    let next_run = if enabled {
+        compute_next_run(&trigger_type, &trigger_config, metadata.as_deref(), now)
+    } else {
+        None
+    };
+
+    conn.execute(
+        r#"
+INSERT INTO tasks (
+  id, name, description,
+  trigger_type, trigger_config,
+  action_type, action_config,
+  enabled, last_run, next_run, metadata,
+  created_at, updated_at, priority
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, NULL, ?, ?, ?, NULL, ?)
+"#,
+        params![
+            id,
+            name,
+            description,
+            trigger_type,
+            trigger_config,
+            action_type,
+            action_config,
+            if enabled { 1 } else { 0 },
+            next_run,
+            metadata,
+            now,
+            priority.unwrap_or(0)
+        ],
+    )
+    .map_err(|e| format!("failed to insert task: {e}"))?;
+
+    Ok(id)
+}
+
+// 内置任务模板：给新用户一键创建的起点，配置必须能通过 validate_trigger_config
+struct PresetDef {
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    trigger_type: &'static str,
+    trigger_config: &'static str,
+    action_type: &'static str,
+    action_config: &'static str,
+}
+
+const PRESETS: [PresetDef; 3] = [
+    PresetDef {
+        id: "hourly_water",
+        name: "喝水提醒",
+        description: "每小时提醒一次喝水",
+        trigger_type: "interval",
+        trigger_config: r#"{"type":"interval","seconds":3600}"#,
+        action_type: "notification",
+        action_config: r#"{"type":"notification","title":"喝水时间到","body":"起来喝杯水，活动一下吧～","channel":"both"}"#,
+    },
+    PresetDef {
+        id: "daily_standup",
+        name: "每日站会",
+        description: "每天上午 9:30 提醒参加站会",
+        trigger_type: "cron",
+        trigger_config: r#"{"type":"cron","expression":"30 9 * * *"}"#,
+        action_type: "notification",
+        action_config: r#"{"type":"notification","title":"每日站会","body":"站会马上开始，准备一下今天的进展吧"}"#,
+    },
+    PresetDef {
+        id: "weekday_focus",
+        name: "工作日专注提示",
+        description: "工作日下午 2 点提醒进入专注时段",
+        trigger_type: "cron",
+        trigger_config: r#"{"type":"cron","expression":"0 14 * * 1-5"}"#,
+        action_type: "pet_reaction",
+        action_config: r#"{"type":"pet_reaction","animation":"work"}"#,
+    },
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetTask {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub trigger: ApiTrigger,
+    pub action: ApiAction,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PresetOverrides {
+    name: Option<String>,
+    description: Option<String>,
+    enabled: Option<bool>,
+    metadata: Option<serde_json::Value>,
+    /// 浅合并到模板的 trigger config 上（例如只改 seconds）
+    trigger_config: Option<serde_json::Map<String, serde_json::Value>>,
+    action_config: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+fn merge_config(
+    base: &str,
+    patch: Option<serde_json::Map<String, serde_json::Value>>,
+) -> Result<String, String> {
+    let Some(patch) = patch else {
+        return Ok(base.to_string());
+    };
+    let mut value: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(base).map_err(|e| format!("invalid preset config: {e}"))?;
+    for (key, v) in patch {
+        // type 决定了配置的结构，不允许被覆盖
+        if key != "type" {
+            value.insert(key, v);
+        }
+    }
+    serde_json::to_string(&value).map_err(|e| format!("failed to encode config: {e}"))
+}
+
+#[tauri::command]
+pub fn scheduler_list_presets() -> Vec<PresetTask> {
+    PRESETS
+        .iter()
+        .map(|p| PresetTask {
+            id: p.id.to_string(),
+            name: p.name.to_string(),
+            description: p.description.to_string(),
+            trigger: ApiTrigger {
+                r#type: p.trigger_type.to_string(),
+                config: TriggerConfig::from_db(p.trigger_type, p.trigger_config),
+            },
+            action: ApiAction {
+                r#type: p.action_type.to_string(),
+                config: ActionConfig::from_db(p.action_type, p.action_config),
+            },
+        })
+        .collect()
+}
+
+/// 用模板创建任务，overrides 可改名称/描述/启用状态/metadata 并浅合并 trigger/action 配置
+#[tauri::command]
+pub fn scheduler_create_from_preset(
+    app: AppHandle,
+    preset_id: String,
+    overrides: Option<serde_json::Value>,
+) -> Result<String, String> {
+    let preset = PRESETS
+        .iter()
+        .find(|p| p.id == preset_id)
+        .ok_or_else(|| format!("unknown preset: {preset_id}"))?;
+
+    let overrides: PresetOverrides = match overrides {
+        Some(v) => serde_json::from_value(v).map_err(|e| format!("invalid overrides: {e}"))?,
+        None => PresetOverrides::default(),
+    };
+
+    let trigger = parse_stored_config::<TriggerConfig>(
+        preset.trigger_type,
+        &merge_config(preset.trigger_config, overrides.trigger_config)?,
+    )
+    .map_err(|e| format!("invalid trigger config: {e}"))?;
+    let action = parse_stored_config::<ActionConfig>(
+        preset.action_type,
+        &merge_config(preset.action_config, overrides.action_config)?,
+    )
+    .map_err(|e| format!("invalid action config: {e}"))?;
+    let metadata = overrides
+        .metadata
+        .map(|m| serde_json::to_string(&m))
+        .transpose()
+        .map_err(|e| format!("failed to encode metadata: {e}"))?;
+
+    scheduler_create_task(
+        app,
+        overrides.name.unwrap_or_else(|| preset.name.to_string()),
+        Some(
+            overrides
+                .description
+                .unwrap_or_else(|| preset.description.to_string()),
+        ),
+        trigger,
+        action,
+        overrides.enabled.unwrap_or(true),
+        metadata,
+        None,
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CrontabImportReport {
+    pub created_ids: Vec<String>,
+    /// "line N: 原因"，解析失败的行不影响其余行导入
+    pub errors: Vec<String>,
+}
+
+/// crontab 里常见的 @ 宏，展开成 5 段表达式
+fn expand_cron_macro(token: &str) -> Option<&'static str> {
+    match token {
+        "@hourly" => Some("0 * * * *"),
+        "@daily" | "@midnight" => Some("0 0 * * *"),
+        "@weekly" => Some("0 0 * * 0"),
+        "@monthly" => Some("0 0 1 * *"),
+        "@yearly" | "@annually" => Some("0 0 1 1 *"),
+        _ => None,
+    }
+}
+
+/// 解析一行 `<cron 5 段> <任务名>`，返回 (表达式, 任务名)
+fn parse_crontab_line(line: &str) -> Result<(String, String), String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (expression, rest) = match tokens.first() {
+        Some(first) if first.starts_with('@') => {
+            let expr = expand_cron_macro(first)
+                .ok_or_else(|| format!("unsupported cron macro: {first}"))?;
+            (expr.to_string(), &tokens[1..])
+        }
+        _ if tokens.len() >= 5 => (tokens[..5].join(" "), &tokens[5..]),
+        _ => return Err("expected `<minute> <hour> <day> <month> <weekday> <name>`".to_string()),
+    };
+    if rest.is_empty() {
+        return Err("missing task name".to_string());
+    }
+    parse_cron_schedule(&expression)?;
+    Ok((expression, rest.join(" ")))
+}
+
+/// 把 crontab 文本批量导入为 cron 任务，所有任务共用 default_action
+#[tauri::command]
+pub fn scheduler_import_crontab(
+    app: AppHandle,
+    text: String,
+    default_action: ActionConfig,
+) -> Result<CrontabImportReport, String> {
+    let mut report = CrontabImportReport {
+        created_ids: Vec::new(),
+        errors: Vec::new(),
+    };
+    for (index, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        // 跳过空行、注释和 `SHELL=/bin/sh` 这类环境变量行
+        if line.is_empty()
+            || line.starts_with('#')
+            || line
+                .split_whitespace()
+                .next()
+                .is_some_and(|t| t.contains('='))
+        {
+            continue;
+        }
+
+        let created = parse_crontab_line(line).and_then(|(expression, name)| {
+            scheduler_create_task(
+                app.clone(),
+                name,
+                None,
+                TriggerConfig::Cron(CronTriggerConfig {
+                    expression,
+                    timezone: None,
+                }),
+                default_action.clone(),
+                true,
+                None,
+                None,
+            )
+        });
+        match created {
+            Ok(id) => report.created_ids.push(id),
+            Err(err) => report.errors.push(format!("line {}: {err}", index + 1)),
+        }
+    }
+    Ok(report)
+}
+
+// 任务导出/导入包的格式版本；结构变化时递增
+const TASK_BUNDLE_VERSION: u32 = 1;
+const MAX_TASK_NAME_CHARS: usize = 200;
+const MAX_TASK_DESCRIPTION_CHARS: usize = 2_000;
+
+#[derive(Debug, Deserialize)]
+struct TaskBundle {
+    version: u32,
+    tasks: Vec<serde_json::Value>,
+}
+
+/// 包里的单个任务；字段与 scheduler_create_task 的参数一一对应
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleTask {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    trigger: TriggerConfig,
+    action: ActionConfig,
+    #[serde(default)]
+    metadata: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleError {
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleValidation {
+    pub version_ok: bool,
+    pub task_count: usize,
+    pub errors: Vec<BundleError>,
+}
+
+fn validate_bundle_task(raw: &serde_json::Value) -> Result<(), String> {
+    let task = BundleTask::deserialize(raw).map_err(|e| e.to_string())?;
+    if task.name.trim().is_empty() {
+        return Err("name must not be empty".to_string());
+    }
+    if task.name.chars().count() > MAX_TASK_NAME_CHARS {
+        return Err(format!("name exceeds {MAX_TASK_NAME_CHARS} characters"));
+    }
+    if task
+        .description
+        .as_ref()
+        .is_some_and(|d| d.chars().count() > MAX_TASK_DESCRIPTION_CHARS)
+    {
+        return Err(format!(
+            "description exceeds {MAX_TASK_DESCRIPTION_CHARS} characters"
+        ));
+    }
+    if task.metadata.as_ref().is_some_and(|m| !m.is_object()) {
+        return Err("metadata must be an object".to_string());
+    }
+    let (trigger_type, trigger_config) = task.trigger.to_db()?;
+    validate_trigger_config(&trigger_type, &trigger_config)?;
+    task.action.to_db()?;
+    Ok(())
+}
+
+/// 导入前的预检：逐条校验任务，不写数据库；整体不是合法 JSON 时直接报错
+#[tauri::command]
+pub fn scheduler_validate_bundle(json: String) -> Result<BundleValidation, String> {
+    let bundle: TaskBundle =
+        serde_json::from_str(&json).map_err(|e| format!("invalid bundle: {e}"))?;
+
+    let errors = bundle
+        .tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, raw)| {
+            validate_bundle_task(raw)
+                .err()
+                .map(|message| BundleError { index, message })
+        })
+        .collect();
+
+    Ok(BundleValidation {
+        version_ok: bundle.version == TASK_BUNDLE_VERSION,
+        task_count: bundle.tasks.len(),
+        errors,
+    })
+}
+
+#[tauri::command]
+pub fn scheduler_get_task(app: AppHandle, id: String) -> Result<ApiTask, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+SELECT {TASK_COLUMNS}
+FROM tasks
+WHERE id = ?
+"#
+        ))
+        .map_err(|e| format!("failed to prepare get task: {e}"))?;
+
+    let row = stmt
+        .query_row(params![id], task_from_row)
+        .map_err(|e| format!("task not found: {e}"))?;
+
+    Ok(row_to_api_task_with_preview(row, now_ms()))
+}
+
+// 单条 SQL 的 IN (...) 最多放多少个 id，低于 SQLite 默认的变量数上限
+const TASK_ID_CHUNK_SIZE: usize = 500;
+
+/// 按 id 批量取任务，结果顺序与传入顺序一致；不存在的 id 直接跳过
+#[tauri::command]
+pub fn scheduler_get_tasks_by_ids(
+    app: AppHandle,
+    ids: Vec<String>,
+) -> Result<Vec<ApiTask>, String> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut found: HashMap<String, DbTaskRow> = HashMap::new();
+    for chunk in ids.chunks(TASK_ID_CHUNK_SIZE) {
+        let placeholders = vec!["?"; chunk.len()].join(", ");
+        let sql = format!(
+            r#"
+SELECT {TASK_COLUMNS}
+FROM tasks
+WHERE id IN ({placeholders})
+"#
+        );
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| format!("failed to prepare tasks by ids: {e}"))?;
+        let rows = stmt
+            .query_map(params_from_iter(chunk.iter()), task_from_row)
+            .map_err(|e| format!("failed to query tasks by ids: {e}"))?;
+        for row in rows {
+            let row = row.map_err(|e| format!("task map error: {e}"))?;
+            found.insert(row.id.clone(), row);
+        }
+    }
+
+    Ok(ids
+        .iter()
+        .filter_map(|id| found.get(id).cloned().map(row_to_api_task))
+        .collect())
+}
+
+// 滚动平均耗时统计最近多少次执行
+const DESCRIBE_DURATION_WINDOW: i64 = 20;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskDescription {
+    pub task: ApiTask,
+    pub avg_duration_ms: Option<f64>,
+    pub sampled_executions: i64,
+    pub slow_executions: i64,
+}
+
+#[tauri::command]
+pub fn scheduler_describe_task(app: AppHandle, id: String) -> Result<TaskDescription, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let task = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+
+    let (avg_duration_ms, sampled_executions, slow_executions) = conn
+        .query_row(
+            r#"
+SELECT AVG(duration), COUNT(*), COALESCE(SUM(slow), 0)
+FROM (
+  SELECT duration, slow
+  FROM task_executions
+  WHERE task_id = ? AND duration IS NOT NULL
+  ORDER BY started_at DESC
+  LIMIT ?
+)
+"#,
+            params![id, DESCRIBE_DURATION_WINDOW],
+            |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+        )
+        .map_err(|e| format!("failed to compute task duration stats: {e}"))?;
+
+    Ok(TaskDescription {
+        task: row_to_api_task(task),
+        avg_duration_ms,
+        sampled_executions,
+        slow_executions,
+    })
+}
+
+// 有效排期里在 next_run 之后再往后列出的次数
+const EFFECTIVE_SCHEDULE_FOLLOWING_RUNS: usize = 5;
+
+/// 任务的完整排期：触发器配置和 metadata 里影响排期的字段全部解析后的结果
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectiveSchedule {
+    /// 触发器类型
+    pub kind: String,
+    /// 固定周期（interval 已应用 interval 下限；weekly 为 N 周），其余为空
+    pub cadence_seconds: Option<i64>,
+    /// 规范化后实际交给 cron crate 的 6 段表达式（补了秒字段、改写了星期）
+    pub cron_expression: Option<String>,
+    /// 实际生效的时区；elapsed 模式的 interval 等与时区无关的触发器为空
+    pub timezone: Option<String>,
+    /// 活跃时段；任务还不能配置活跃时段，目前恒为空
+    pub active_window: Option<String>,
+    pub skip_rules: Option<SkipRules>,
+    /// 实际生效的 misfirePolicy，未设置时为 "run_once"
+    pub misfire_policy: String,
+    /// 不属于触发时间本身，但决定到期时是否真的执行，详情页一并展示
+    pub overlap_policy: Option<String>,
+    /// 已停用的任务为"现在启用的话"的下一次
+    pub next_run: Option<i64>,
+    pub following_runs: Vec<i64>,
+}
+
+/// 详情页用的一站式排期视图；触发器配置或 metadata 解析不了时直接报错
+#[tauri::command]
+pub fn scheduler_get_effective_schedule(
+    app: AppHandle,
+    id: String,
+) -> Result<EffectiveSchedule, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let task = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    let trigger = parse_stored_config::<TriggerConfig>(&task.trigger_type, &task.trigger_config)
+        .map_err(|e| format!("invalid trigger config: {e}"))?;
+    let options = task
+        .metadata
+        .as_deref()
+        .map(serde_json::from_str::<TaskOptions>)
+        .transpose()
+        .map_err(|e| format!("invalid metadata: {e}"))?
+        .unwrap_or_default();
+
+    let zone = |name: Option<&str>| resolve_timezone(name).map(|tz| Some(tz.name().to_string()));
+    let (cadence_seconds, cron_expression, timezone) = match &trigger {
+        TriggerConfig::Interval(cfg) => (
+            Some(
+                cfg.seconds
+                    .max(MIN_INTERVAL_SECONDS.load(Ordering::Relaxed)),
+            ),
+            None,
+            match cfg.interval_mode.as_deref() {
+                Some("wallclock") => zone(cfg.timezone.as_deref())?,
+                _ => None,
+            },
+        ),
+        TriggerConfig::Cron(cfg) => (
+            None,
+            Some(normalize_cron_expression(&cfg.expression)?),
+            zone(cfg.timezone.as_deref())?,
+        ),
+        TriggerConfig::Weekly(cfg) => (
+            Some(cfg.every_n_weeks * 7 * 86_400),
+            None,
+            zone(cfg.timezone.as_deref())?,
+        ),
+        TriggerConfig::Lunar(cfg) => (None, None, zone(cfg.timezone.as_deref())?),
+        TriggerConfig::Solar(cfg) => (None, None, zone(cfg.timezone.as_deref())?),
+        _ => (None, None, None),
+    };
+
+    let metadata = task.metadata.as_deref();
+    let next_run = if task.enabled {
+        task.next_run
+    } else {
+        compute_next_run(&task.trigger_type, &task.trigger_config, metadata, now_ms())
+    };
+    let mut following_runs = Vec::new();
+    let mut at = next_run;
+    while following_runs.len() < EFFECTIVE_SCHEDULE_FOLLOWING_RUNS {
+        let Some(prev) = at else {
+            break;
+        };
+        at = compute_next_run(&task.trigger_type, &task.trigger_config, metadata, prev)
+            .filter(|&n| n > prev);
+        following_runs.extend(at);
+    }
+
+    Ok(EffectiveSchedule {
+        kind: trigger.type_name().to_string(),
+        cadence_seconds,
+        cron_expression,
+        timezone,
+        active_window: None,
+        skip_rules: options.skip_rules,
+        misfire_policy: options
+            .misfire_policy
+            .unwrap_or_else(|| "run_once".to_string()),
+        overlap_policy: options.overlap_policy,
+        next_run,
+        following_runs,
+    })
+}
+
+#[tauri::command]
+pub fn scheduler_get_all_tasks(
+    app: AppHandle,
+    order_by: Option<String>,
+) -> Result<Vec<ApiTask>, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let order_clause = match order_by.as_deref().unwrap_or("created") {
+        "created" => "created_at DESC",
+        // 未手动排序过的任务排在最后，内部仍按创建时间
+        "sort" => "sort_order IS NULL, sort_order ASC, created_at DESC",
+        "next_run" => "next_run IS NULL, next_run ASC, created_at DESC",
+        other => return Err(format!("unknown order_by: {other}")),
+    };
+
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+SELECT {TASK_COLUMNS}
+FROM tasks
+ORDER BY {order_clause}
+"#
+        ))
+        .map_err(|e| format!("failed to prepare list tasks: {e}"))?;
+
+    let rows = stmt
+        .query_map([], task_from_row)
+        .map_err(|e| format!("failed to query tasks: {e}"))?;
+
+    let now = now_ms();
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row_to_api_task_with_preview(
+            row.map_err(|e| format!("task map error: {e}"))?,
+            now,
+        ));
+    }
+    // 顺带清掉已删除任务的缓存，缓存大小不超过任务总数
+    let ids: BTreeSet<&str> = out.iter().map(|t| t.id.as_str()).collect();
+    PREVIEW_CACHE
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .retain(|id, _| ids.contains(id.as_str()));
+    Ok(out)
+}
+
+/// 立即同步跑一轮调度；与后台循环共用 tick 锁，不会和正在进行的 tick 重复触发同一批任务。
+/// 安全模式下直接报错，须先 scheduler_exit_safe_mode
+#[tauri::command]
+pub fn scheduler_tick_now(
+    app: AppHandle,
+    runner: State<'_, SchedulerRunner>,
+) -> Result<TickSummary, String> {
+    if runner.safe_mode.load(Ordering::SeqCst) {
+        return Err("scheduler is in safe mode; call scheduler_exit_safe_mode first".to_string());
+    }
+    let _paused = runner.pause_ticks();
+    tick(&app, runner.clock)
+}
+
+/// 本次启动以来后台循环的间隔统计；最长间隔远大于轮询间隔说明机器睡眠过
+#[tauri::command]
+pub fn scheduler_get_tick_diagnostics(runner: State<'_, SchedulerRunner>) -> TickDiagnostics {
+    let stats = runner.tick_stats.lock().unwrap_or_else(|p| p.into_inner());
+    let gaps = stats.total_ticks.saturating_sub(1);
+    TickDiagnostics {
+        longest_gap_ms: stats.longest_gap_ms,
+        avg_gap_ms: if gaps == 0 {
+            0.0
+        } else {
+            stats.total_gap_ms as f64 / gaps as f64
+        },
+        total_ticks: stats.total_ticks,
+        missed_estimate: stats.missed_estimate,
+    }
+}
+
+/// 排查"触发时间不对"用：原样暴露 `try_compute_next_run` 的计算过程。
+/// cron 会给出实际交给 `cron` crate 的 6 段表达式（补了秒字段、改写了星期）；不叠加 skipRules
+#[tauri::command]
+pub fn scheduler_debug_compute(
+    trigger_type: String,
+    trigger_config: String,
+    from_ms: i64,
+) -> DebugCompute {
+    let timezone_name = serde_json::from_str::<serde_json::Value>(&trigger_config)
+        .ok()
+        .and_then(|v| v.get("timezone")?.as_str().map(str::to_string));
+    let tz = resolve_timezone(timezone_name.as_deref());
+
+    let mut errors: Vec<String> = tz.as_ref().err().cloned().into_iter().collect();
+    let effective_expression = if trigger_type == "cron" {
+        serde_json::from_str::<CronTriggerConfig>(&trigger_config)
+            .map_err(|e| format!("invalid cron trigger config: {e}"))
+            .and_then(|cfg| normalize_cron_expression(&cfg.expression))
+            .map_err(|e| errors.push(e))
+            .ok()
+    } else {
+        None
+    };
+
+    let next_run_ms = match try_compute_next_run(&trigger_type, &trigger_config, from_ms) {
+        Ok(next) => next,
+        Err(e) => {
+            // cron 表达式错误上面已经记过一次
+            if !errors.contains(&e) {
+                errors.push(e);
+            }
+            None
+        }
+    };
+    let next_run_iso = match (next_run_ms, &tz) {
+        (Some(ms), Ok(tz)) => Utc
+            .timestamp_millis_opt(ms)
+            .single()
+            .map(|dt| dt.with_timezone(tz).to_rfc3339()),
+        _ => None,
+    };
+
+    DebugCompute {
+        next_run_ms,
+        next_run_iso,
+        effective_expression,
+        resolved_timezone: tz.as_ref().ok().map(|tz| tz.name().to_string()),
+        error: (!errors.is_empty()).then(|| errors.join("; ")),
+    }
+}
+
+const SIMULATE_RANGE_MAX_FIRES: usize = 10_000;
+
+/// 回放触发器在 [from_ms, to_ms] 内本该触发的所有时间点（只读，不碰任何任务），
+/// 用来在启用前核对 cron 的实际日期；最多 SIMULATE_RANGE_MAX_FIRES 个
+#[tauri::command]
+pub fn scheduler_simulate_range(
+    trigger_type: String,
+    trigger_config: String,
+    from_ms: i64,
+    to_ms: i64,
+) -> Result<Vec<i64>, String> {
+    if to_ms < from_ms {
+        return Err("to_ms must be >= from_ms".to_string());
+    }
+
+    // 日历类触发器算的是"之后"的下一次，从 from_ms - 1 起算才能包含恰好落在 from_ms 上的触发；
+    // interval 以 from_ms 为起点逐步往后推
+    let mut out = Vec::new();
+    let mut cursor = if trigger_type == "interval" {
+        from_ms
+    } else {
+        from_ms - 1
+    };
+    while out.len() < SIMULATE_RANGE_MAX_FIRES {
+        match try_compute_next_run(&trigger_type, &trigger_config, cursor)? {
+            Some(next) if next <= to_ms && next > cursor => {
+                out.push(next);
+                cursor = next;
+            }
+            _ => break,
+        }
+    }
+    Ok(out)
+}
+
+/// 只存在于内存里的调度器状态（不落库），与 `SchedulerConfig` 一起返回，便于测试和调试界面一次读全
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeState {
+    /// 调度器时钟的当前时间（mock-clock 下可能不是系统时间）
+    pub now_ms: i64,
+    pub running: bool,
+    pub safe_mode: bool,
+    pub on_battery: bool,
+    /// 正在执行中的任务 id
+    pub in_flight_task_ids: Vec<String>,
+    /// compute_next_run 当前使用的 interval 下限
+    pub min_interval_seconds: i64,
+    /// 后台循环最近一次转完一圈的时间；尚未转过为 null
+    pub last_loop_ms: Option<i64>,
+    pub tick_interval_ms: u64,
+    pub last_idle_seconds: i64,
+    pub last_idle_reported_at: Option<i64>,
+    /// 组合触发器可见的已上报事件（事件名 -> 最近一次时间）
+    pub seen_events: BTreeMap<String, i64>,
+    /// 当前有 channel 订阅的任务数
+    pub subscribed_task_count: usize,
+    pub config: SchedulerConfig,
+}
+
+#[tauri::command]
+pub fn scheduler_get_runtime_state(
+    app: AppHandle,
+    runner: State<'_, SchedulerRunner>,
+) -> Result<RuntimeState, String> {
+    let config = scheduler_get_config(app)?;
+    let last_loop_ms = runner.last_loop_ms.load(Ordering::Relaxed);
+    let last_idle_reported_at = runner.state.last_idle_reported_at.load(Ordering::Relaxed);
+
+    Ok(RuntimeState {
+        now_ms: runner.clock.now_ms(),
+        running: runner.is_started.load(Ordering::SeqCst) && !runner.stop.load(Ordering::SeqCst),
+        safe_mode: runner.safe_mode.load(Ordering::SeqCst),
+        on_battery: ON_BATTERY.load(Ordering::Relaxed),
+        in_flight_task_ids: IN_FLIGHT
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .keys()
+            .cloned()
+            .collect(),
+        min_interval_seconds: MIN_INTERVAL_SECONDS.load(Ordering::Relaxed),
+        last_loop_ms: (last_loop_ms > 0).then_some(last_loop_ms),
+        tick_interval_ms: tick_interval_ms(),
+        last_idle_seconds: runner.state.last_idle_seconds.load(Ordering::Relaxed),
+        last_idle_reported_at: (last_idle_reported_at > 0).then_some(last_idle_reported_at),
+        seen_events: runner
+            .state
+            .seen_events
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .clone(),
+        subscribed_task_count: runner
+            .state
+            .task_subscribers
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .len(),
+        config,
+    })
+}
+
+const TICK_INTERVAL_SETTING_KEY: &str = "tick_interval_ms";
+
+fn default_tick_interval_ms() -> u64 {
+    SCHEDULER_TICK_MS
+}
+
+fn apply_tick_interval_ms(ms: u64) {
+    let previous = TICK_INTERVAL_MS.swap(ms, Ordering::Relaxed);
+    if previous != ms {
+        log::info!("[Scheduler] tick interval changed from {previous}ms to {ms}ms");
+    }
+}
+
+/// 读取持久化的轮询间隔并同步到 TICK_INTERVAL_MS
+fn load_tick_interval_ms(conn: &Connection) -> Result<u64, String> {
+    let ms = get_setting(conn, TICK_INTERVAL_SETTING_KEY)?
+        .and_then(|raw| raw.parse::<u64>().ok())
+        .unwrap_or(SCHEDULER_TICK_MS)
+        .clamp(MIN_TICK_INTERVAL_MS, MAX_TICK_INTERVAL_MS);
+    apply_tick_interval_ms(ms);
+    Ok(ms)
+}
+
+/// 调整后台轮询间隔（毫秒）并持久化，下一圈生效；超出范围的值会被夹到 [50, 60000]，返回实际生效的值
+#[tauri::command]
+pub fn scheduler_set_tick_interval(app: AppHandle, ms: u64) -> Result<u64, String> {
+    let ms = ms.clamp(MIN_TICK_INTERVAL_MS, MAX_TICK_INTERVAL_MS);
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    set_setting(&conn, TICK_INTERVAL_SETTING_KEY, &ms.to_string())?;
+    apply_tick_interval_ms(ms);
+    Ok(ms)
+}
+
+#[tauri::command]
+pub fn scheduler_get_tick_interval() -> u64 {
+    tick_interval_ms()
+}
+
+/// 启动时检测到上次崩溃会进入安全模式（后台不自动执行任务），UI 加载后用它补查状态
+#[tauri::command]
+pub fn scheduler_get_safe_mode(runner: State<'_, SchedulerRunner>) -> bool {
+    runner.safe_mode.load(Ordering::SeqCst)
+}
+
+/// 用户检查过任务后退出安全模式，恢复自动调度
+#[tauri::command]
+pub fn scheduler_exit_safe_mode(runner: State<'_, SchedulerRunner>) {
+    if runner.safe_mode.swap(false, Ordering::SeqCst) {
+        log::info!("[Scheduler] safe mode exited by user");
+    }
+}
+
+/// 所有已启用且有 next_run 的任务距下次执行的毫秒数，用调度器同一个时钟计算；
+/// 已过期（待执行）的返回负值，由 UI 显示"即将执行"
+#[tauri::command]
+pub fn scheduler_get_countdowns(app: AppHandle) -> Result<Vec<(String, i64)>, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT id, next_run - ?
+FROM tasks
+WHERE enabled = 1 AND next_run IS NOT NULL
+ORDER BY next_run ASC
+"#,
+        )
+        .map_err(|e| format!("failed to prepare countdowns: {e}"))?;
+
+    let rows = stmt
+        .query_map(params![now_ms()], |r| Ok((r.get(0)?, r.get(1)?)))
+        .map_err(|e| format!("failed to query countdowns: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row.map_err(|e| format!("countdown map error: {e}"))?);
+    }
+    Ok(out)
+}
+
+// 非 interval 触发器按未来一周实际展开的次数求日均，周/农历类任务也能得到有意义的值
+const DAILY_LOAD_SAMPLE_DAYS: i64 = 7;
+const DAILY_LOAD_MAX_STEPS: usize = 20_000;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyLoadEstimate {
+    pub total_per_day: f64,
+    /// (task id, 每天大约执行次数)，按次数从高到低
+    pub per_task: Vec<(String, f64)>,
+}
+
+fn estimate_runs_per_day(
+    trigger_type: &str,
+    trigger_config: &str,
+    metadata: Option<&str>,
+    now: i64,
+) -> f64 {
+    if trigger_type == "interval" {
+        if let Ok(cfg) = serde_json::from_str::<IntervalTriggerConfig>(trigger_config) {
+            let seconds = cfg
+                .seconds
+                .max(MIN_INTERVAL_SECONDS.load(Ordering::Relaxed))
+                .max(1);
+            return 86_400.0 / seconds as f64;
+        }
+    }
+
+    let horizon = now + DAILY_LOAD_SAMPLE_DAYS * 86_400_000;
+    let mut count = 0usize;
+    let mut at = compute_next_run(trigger_type, trigger_config, metadata, now);
+    while let Some(t) = at {
+        if t > horizon || count >= DAILY_LOAD_MAX_STEPS {
+            break;
+        }
+        count += 1;
+        at = compute_next_run(trigger_type, trigger_config, metadata, t).filter(|&n| n > t);
+    }
+    count as f64 / DAILY_LOAD_SAMPLE_DAYS as f64
+}
+
+/// 只读估算：所有已启用的定时任务每天大约会执行多少次，方便在启用前发现每秒级的任务
+#[tauri::command]
+pub fn scheduler_estimate_daily_load(app: AppHandle) -> Result<DailyLoadEstimate, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT id, trigger_type, trigger_config, metadata
+FROM tasks
+WHERE enabled = 1
+"#,
+        )
+        .map_err(|e| format!("failed to prepare daily load: {e}"))?;
+
+    let rows = stmt
+        .query_map([], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(|e| format!("failed to query daily load: {e}"))?;
+
+    let now = now_ms();
+    let mut per_task = Vec::new();
+    for row in rows {
+        let (task_id, trigger_type, trigger_config, metadata) =
+            row.map_err(|e| format!("daily load map error: {e}"))?;
+        if !is_time_based_trigger(&trigger_type) {
+            continue;
+        }
+        let per_day =
+            estimate_runs_per_day(&trigger_type, &trigger_config, metadata.as_deref(), now);
+        per_task.push((task_id, per_day));
+    }
+    per_task.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    Ok(DailyLoadEstimate {
+        total_per_day: per_task.iter().map(|(_, n)| n).sum(),
+        per_task,
+    })
+}
+
+const AGENDA_MAX_DAYS: i64 = 366;
+// 单个任务最多展开的次数，防止每秒级的任务把循环拖死
+const AGENDA_MAX_ENTRIES_PER_TASK: usize = 500;
+const AGENDA_MAX_ENTRIES: usize = 2_000;
+
+/// 从 now 到 horizon 之间的触发时间，单个任务最多 AGENDA_MAX_ENTRIES_PER_TASK 次
+fn upcoming_fires(
+    trigger_type: &str,
+    trigger_config: &str,
+    metadata: Option<&str>,
+    next_run: Option<i64>,
+    now: i64,
+    horizon: i64,
+) -> Vec<i64> {
+    // 第一次优先用库里的 next_run（elapsed 间隔是从上次执行算起的），之后逐次往后推
+    let mut at = next_run
+        .filter(|&t| t >= now)
+        .or_else(|| compute_next_run(trigger_type, trigger_config, metadata, now));
+    let mut out = Vec::new();
+    while let Some(t) = at {
+        if t > horizon || out.len() >= AGENDA_MAX_ENTRIES_PER_TASK {
+            break;
+        }
+        out.push(t);
+        at = compute_next_run(trigger_type, trigger_config, metadata, t).filter(|&n| n > t);
+    }
+    out
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgendaEntry {
+    pub task_id: String,
+    pub name: String,
+    pub at_ms: i64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AgendaDay {
+    /// tz 下的本地日期 "YYYY-MM-DD"
+    pub date: String,
+    pub entries: Vec<AgendaEntry>,
+}
+
+/// 未来 days 天内所有已启用任务的触发时间，按 tz（缺省为系统时区）的本地日期分组；
+/// 总条数超过上限时只保留最早的部分
+#[tauri::command]
+pub fn scheduler_get_agenda(
+    app: AppHandle,
+    days: i64,
+    tz: Option<String>,
+) -> Result<Vec<AgendaDay>, String> {
+    if !(1..=AGENDA_MAX_DAYS).contains(&days) {
+        return Err(format!("days must be between 1 and {AGENDA_MAX_DAYS}"));
+    }
+    let tz = resolve_timezone(tz.as_deref())?;
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT id, name, trigger_type, trigger_config, metadata, next_run
+FROM tasks
+WHERE enabled = 1
+"#,
+        )
+        .map_err(|e| format!("failed to prepare agenda: {e}"))?;
+
+    let rows = stmt
+        .query_map([], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, String>(3)?,
+                r.get::<_, Option<String>>(4)?,
+                r.get::<_, Option<i64>>(5)?,
+            ))
+        })
+        .map_err(|e| format!("failed to query agenda: {e}"))?;
+
+    let now = now_ms();
+    let horizon = now + days * 86_400_000;
+    let mut entries = Vec::new();
+    for row in rows {
+        let (task_id, name, trigger_type, trigger_config, metadata, next_run) =
+            row.map_err(|e| format!("agenda map error: {e}"))?;
+
+        for at_ms in upcoming_fires(
+            &trigger_type,
+            &trigger_config,
+            metadata.as_deref(),
+            next_run,
+            now,
+            horizon,
+        ) {
+            entries.push(AgendaEntry {
+                task_id: task_id.clone(),
+                name: name.clone(),
+                at_ms,
+            });
+        }
+    }
+
+    entries.sort_by_key(|e| e.at_ms);
+    entries.truncate(AGENDA_MAX_ENTRIES);
+
+    let mut out: Vec<AgendaDay> = Vec::new();
+    for entry in entries {
+        let Some(local) = Utc.timestamp_millis_opt(entry.at_ms).single() else {
+            continue;
+        };
+        let date = local.with_timezone(&tz).format("%Y-%m-%d").to_string();
+        match out.last_mut() {
+            Some(day) if day.date == date => day.entries.push(entry),
+            _ => out.push(AgendaDay {
+                date,
+                entries: vec![entry],
+            }),
+        }
+    }
+    Ok(out)
+}
+
+/// 未来 days 天内所有已启用任务的触发次数按 tz（缺省为系统时区）的本地小时分桶，
+/// 返回 24 个计数（下标即小时），用来看任务是否都挤在同一个钟点
+#[tauri::command]
+pub fn scheduler_get_hourly_histogram(
+    app: AppHandle,
+    days: i64,
+    tz: Option<String>,
+) -> Result<Vec<i64>, String> {
+    if !(1..=AGENDA_MAX_DAYS).contains(&days) {
+        return Err(format!("days must be between 1 and {AGENDA_MAX_DAYS}"));
+    }
+    let tz = resolve_timezone(tz.as_deref())?;
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT trigger_type, trigger_config, metadata, next_run
+FROM tasks
+WHERE enabled = 1
+"#,
+        )
+        .map_err(|e| format!("failed to prepare hourly histogram: {e}"))?;
+
+    let rows = stmt
+        .query_map([], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, Option<i64>>(3)?,
+            ))
+        })
+        .map_err(|e| format!("failed to query hourly histogram: {e}"))?;
+
+    let now = now_ms();
+    let horizon = now + days * 86_400_000;
+    let mut buckets = vec![0_i64; 24];
+    for row in rows {
+        let (trigger_type, trigger_config, metadata, next_run) =
+            row.map_err(|e| format!("hourly histogram map error: {e}"))?;
+
+        // upcoming_fires 已按 AGENDA_MAX_ENTRIES_PER_TASK 截断，每秒级的任务不会拖死循环
+        for at_ms in upcoming_fires(
+            &trigger_type,
+            &trigger_config,
+            metadata.as_deref(),
+            next_run,
+            now,
+            horizon,
+        ) {
+            if let Some(at) = Utc.timestamp_millis_opt(at_ms).single() {
+                buckets[at.with_timezone(&tz).hour() as usize] += 1;
+            }
+        }
+    }
+    Ok(buckets)
+}
+
+/// 导出未来 days 天的触发时间为 iCalendar 文本，供导入日历应用。
+/// 间隔固定的任务（每天 9 点、每 2 小时等）合并成一条带 RRULE 的事件，其余逐次展开；
+/// 时间一律用 UTC，避免依赖 VTIMEZONE 定义
+#[tauri::command]
+pub fn scheduler_export_ics(app: AppHandle, days: i64) -> Result<String, String> {
+    if !(1..=AGENDA_MAX_DAYS).contains(&days) {
+        return Err(format!("days must be between 1 and {AGENDA_MAX_DAYS}"));
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT id, name, description, trigger_type, trigger_config, metadata, next_run
+FROM tasks
+WHERE enabled = 1
+ORDER BY created_at ASC
+"#,
+        )
+        .map_err(|e| format!("failed to prepare ics export: {e}"))?;
+
+    let rows = stmt
+        .query_map([], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, String>(3)?,
+                r.get::<_, String>(4)?,
+                r.get::<_, Option<String>>(5)?,
+                r.get::<_, Option<i64>>(6)?,
+            ))
+        })
+        .map_err(|e| format!("failed to query ics export: {e}"))?;
+
+    let now = now_ms();
+    let horizon = now + days * 86_400_000;
+    let stamp = ics_timestamp(now)?;
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//AI Desktop Pet//Scheduler//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    let mut events = 0;
+    for row in rows {
+        let (task_id, name, description, trigger_type, trigger_config, metadata, next_run) =
+            row.map_err(|e| format!("ics export map error: {e}"))?;
+        let fires = upcoming_fires(
+            &trigger_type,
+            &trigger_config,
+            metadata.as_deref(),
+            next_run,
+            now,
+            horizon,
+        );
+        let Some(&first) = fires.first() else {
+            continue;
+        };
+
+        let event = |at: i64, rrule: Option<String>| -> Result<Vec<String>, String> {
+            let mut lines = vec![
+                "BEGIN:VEVENT".to_string(),
+                format!("UID:{task_id}-{at}@ai-desktop-pet"),
+                format!("DTSTAMP:{stamp}"),
+                format!("DTSTART:{}", ics_timestamp(at)?),
+            ];
+            if let Some(rrule) = rrule {
+                lines.push(format!("RRULE:{rrule}"));
+            }
+            lines.push(format!("SUMMARY:{}", ics_escape(&name)));
+            if let Some(description) = description.as_deref().filter(|d| !d.is_empty()) {
+                lines.push(format!("DESCRIPTION:{}", ics_escape(description)));
+            }
+            lines.push("END:VEVENT".to_string());
+            Ok(lines)
+        };
+
+        match ics_rrule(&fires) {
+            Some(rrule) => {
+                lines.extend(event(first, Some(rrule))?);
+                events += 1;
+            }
+            None => {
+                for &at in &fires {
+                    if events >= AGENDA_MAX_ENTRIES {
+                        break;
+                    }
+                    lines.extend(event(at, None)?);
+                    events += 1;
+                }
+            }
+        }
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    // RFC 5545：行尾必须是 CRLF，超过 75 字节的行要折行
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&ics_fold(&line));
+        out.push_str("\r\n");
+    }
+    Ok(out)
+}
+
+fn ics_timestamp(ms: i64) -> Result<String, String> {
+    Utc.timestamp_millis_opt(ms)
+        .single()
+        .map(|dt| dt.format("%Y%m%dT%H%M%SZ").to_string())
+        .ok_or_else(|| format!("invalid timestamp: {ms}"))
+}
+
+/// 相邻触发间隔完全相同时，用 RRULE 表示（COUNT 为展开的次数）；不规则的返回 None
+fn ics_rrule(fires: &[i64]) -> Option<String> {
+    let period = fires.get(1)? - fires[0];
+    if period <= 0 || fires.windows(2).any(|w| w[1] - w[0] != period) {
+        return None;
+    }
+    let (freq, unit) = [
+        ("WEEKLY", 7 * 86_400_000),
+        ("DAILY", 86_400_000),
+        ("HOURLY", 3_600_000),
+        ("MINUTELY", 60_000),
+        ("SECONDLY", 1_000),
+    ]
+    .into_iter()
+    .find(|(_, unit)| period % unit == 0)?;
+    Some(format!(
+        "FREQ={freq};INTERVAL={};COUNT={}",
+        period / unit,
+        fires.len()
+    ))
+}
+
+/// TEXT 值里的 `\`、`;`、`,` 和换行需要转义
+fn ics_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// 按 75 字节折行，续行以一个空格开头；不在 UTF-8 字符中间断开
+fn ics_fold(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        let len = c.len_utf8();
+        // 续行开头的空格也算 1 字节
+        if width + len > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += len;
+    }
+    out
+}
+
+/// 按给定顺序写入 sort_order（0 起递增），供 UI 拖拽排序；任一 id 不存在则整体回滚
+#[tauri::command]
+pub fn scheduler_reorder_tasks(app: AppHandle, ordered_ids: Vec<String>) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    for (index, id) in ordered_ids.iter().enumerate() {
+        let updated = tx
+            .execute(
+                "UPDATE tasks SET sort_order = ? WHERE id = ?",
+                params![index as i64, id],
+            )
+            .map_err(|e| format!("failed to reorder task: {e}"))?;
+        if updated == 0 {
+            return Err(format!("task not found: {id}"));
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(())
+}
+
+fn escape_like(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for ch in input.chars() {
+        if matches!(ch, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+#[tauri::command]
+pub fn scheduler_search_tasks(
+    app: AppHandle,
+    query: String,
+    limit: Option<i64>,
+) -> Result<Vec<ApiTask>, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    // SQLite 的 LIKE 对 ASCII 默认不区分大小写；转义通配符，避免 `_` 被当成任意字符
+    let pattern = format!("%{}%", escape_like(query.trim()));
+
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+SELECT {TASK_COLUMNS}
+FROM tasks
+WHERE name LIKE ?1 ESCAPE '\' OR description LIKE ?1 ESCAPE '\'
+ORDER BY COALESCE(updated_at, created_at) DESC
+LIMIT ?2
+"#
+        ))
+        .map_err(|e| format!("failed to prepare search tasks: {e}"))?;
+
+    let rows = stmt
+        .query_map(params![pattern, limit], task_from_row)
+        .map_err(|e| format!("failed to search tasks: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row_to_api_task(
+            row.map_err(|e| format!("task map error: {e}"))?,
+        ));
+    }
+    Ok(out)
+}
+
+/// 哪些任务引用了某个外部资源，删除工作流或改事件名之前先查一下：
+/// kind 为 workflow（workflow action 的 workflowId）、event（事件触发器的 eventName）、
+/// task（task_control action 的 targetTaskId）；sequence 步骤和组合触发器的子项也会检查
+#[tauri::command]
+pub fn scheduler_find_tasks_referencing(
+    app: AppHandle,
+    kind: String,
+    value: String,
+) -> Result<Vec<ApiTask>, String> {
+    if !matches!(kind.as_str(), "workflow" | "event" | "task") {
+        return Err(format!(
+            "unknown reference kind: {kind} (expected workflow/event/task)"
+        ));
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    // 先用 LIKE 粗筛（按 JSON 编码后的形式匹配），再解析配置精确比较
+    let encoded = serde_json::to_string(&value).map_err(|e| e.to_string())?;
+    let pattern = format!("%{}%", escape_like(&encoded[1..encoded.len() - 1]));
+
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+SELECT {TASK_COLUMNS}
+FROM tasks
+WHERE trigger_config LIKE ?1 ESCAPE '\' OR action_config LIKE ?1 ESCAPE '\'
+ORDER BY created_at ASC
+"#
+        ))
+        .map_err(|e| format!("failed to prepare referencing tasks: {e}"))?;
+
+    let rows = stmt
+        .query_map(params![pattern], task_from_row)
+        .map_err(|e| format!("failed to query referencing tasks: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        let row = row.map_err(|e| format!("task map error: {e}"))?;
+        let referenced = match kind.as_str() {
+            "event" => trigger_references_event(
+                &TriggerConfig::from_db(&row.trigger_type, &row.trigger_config),
+                &value,
+            ),
+            _ => action_references(
+                &ActionConfig::from_db(&row.action_type, &row.action_config),
+                &kind,
+                &value,
+            ),
+        };
+        if referenced {
+            out.push(row_to_api_task(row));
+        }
+    }
+    Ok(out)
+}
+
+fn trigger_references_event(trigger: &TriggerConfig, event_name: &str) -> bool {
+    match trigger {
+        TriggerConfig::Event(cfg) => cfg.event_name == event_name,
+        TriggerConfig::Composite(cfg) => cfg
+            .children
+            .iter()
+            .any(|child| trigger_references_event(child, event_name)),
+        _ => false,
+    }
+}
+
+fn action_references(action: &ActionConfig, kind: &str, value: &str) -> bool {
+    match action {
+        ActionConfig::Workflow(cfg) => kind == "workflow" && cfg.workflow_id == value,
+        ActionConfig::TaskControl(cfg) => kind == "task" && cfg.target_task_id == value,
+        ActionConfig::Sequence(cfg) => cfg
+            .steps
+            .iter()
+            .any(|step| action_references(&step.action, kind, value)),
+        _ => false,
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn scheduler_update_task(
+    app: AppHandle,
+    id: String,
+    name: Option<String>,
+    description: Option<String>,
+    trigger: Option<TriggerConfig>,
+    action: Option<ActionConfig>,
+    enabled: Option<bool>,
+    metadata: Option<String>,
+    priority: Option<i64>,
+) -> Result<(), String> {
+    let (trigger_type, trigger_config) = match trigger.map(|t| t.to_db()).transpose()? {
+        Some((t, c)) => (Some(t), Some(c)),
+        None => (None, None),
+    };
+    let (action_type, action_config) = match action.map(|a| a.to_db()).transpose()? {
+        Some((t, c)) => (Some(t), Some(c)),
+        None => (None, None),
+    };
+
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    // 读取现有任务用于计算 next_run
+    let existing = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+
+    let final_trigger_type = trigger_type
+        .clone()
+        .unwrap_or(existing.trigger_type.clone());
+    let final_trigger_config = trigger_config
+        .clone()
+        .unwrap_or(existing.trigger_config.clone());
+    let final_enabled = enabled.unwrap_or(existing.enabled);
+    if final_enabled && !existing.enabled {
+        check_enabled_task_limit(&conn)?;
+    }
+    if trigger_type.is_some() || trigger_config.is_some() {
+        validate_trigger_config(&final_trigger_type, &final_trigger_config)?;
+    }
+
+    let now = now_ms();
+    let next_run = if final_enabled {
+        compute_next_run(
+            &final_trigger_type,
+            &final_trigger_config,
+            metadata.as_deref().or(existing.metadata.as_deref()),
+            now,
+        )
+    } else {
+        None
+    };
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    tx.execute(
+        r#"
+UPDATE tasks
+SET
+  name = COALESCE(?, name),
+  description = COALESCE(?, description),
+  trigger_type = COALESCE(?, trigger_type),
+  trigger_config = COALESCE(?, trigger_config),
+  action_type = COALESCE(?, action_type),
+  action_config = COALESCE(?, action_config),
+  enabled = COALESCE(?, enabled),
+  metadata = COALESCE(?, metadata),
+  priority = COALESCE(?, priority),
+  next_run = ?,
+  updated_at = ?
+WHERE id = ?
+"#,
+        params![
+            name,
+            description,
+            trigger_type,
+            trigger_config,
+            action_type,
+            action_config,
+            enabled.map(|b| if b { 1 } else { 0 }),
+            metadata,
+            priority,
+            next_run,
+            now,
+            id
+        ],
+    )
+    .map_err(|e| format!("failed to update task: {e}"))?;
+
+    if final_enabled != existing.enabled {
+        record_audit(
+            &tx,
+            &id,
+            enabled_audit_event(final_enabled),
+            Some("scheduler_update_task"),
+        )?;
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SchedulePreview {
+    pub current_next_run: Option<i64>,
+    pub proposed_next_run: Option<i64>,
+}
+
+/// 只读：按 scheduler_update_task 的同一套规则算出改触发器后的 next_run，供 UI 确认
+#[tauri::command]
+pub fn scheduler_preview_update(
+    app: AppHandle,
+    id: String,
+    new_trigger: TriggerConfig,
+) -> Result<SchedulePreview, String> {
+    let (new_trigger_type, new_trigger_config) = new_trigger.to_db()?;
+    validate_trigger_config(&new_trigger_type, &new_trigger_config)?;
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let existing = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    let proposed_next_run = if existing.enabled {
+        compute_next_run(
+            &new_trigger_type,
+            &new_trigger_config,
+            existing.metadata.as_deref(),
+            now_ms(),
+        )
+    } else {
+        None
+    };
+
+    Ok(SchedulePreview {
+        current_next_run: existing.next_run,
+        proposed_next_run,
+    })
+}
+
+/// 执行记录靠 ON DELETE CASCADE 一起删除，连接需先经过 configure_connection
+fn delete_task(conn: &Connection, id: &str) -> Result<usize, String> {
+    conn.execute("DELETE FROM tasks WHERE id = ?", params![id])
+        .map_err(|e| format!("failed to delete task: {e}"))
+}
+
+#[tauri::command]
+pub fn scheduler_delete_task(app: AppHandle, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    delete_task(&conn, &id)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn scheduler_delete_tasks(app: AppHandle, ids: Vec<String>) -> Result<i64, String> {
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    let mut deleted = 0;
+    for id in &ids {
+        deleted += delete_task(&tx, id)?;
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(deleted as i64)
+}
+
+/// 按条件批量删除：`enabled` 过滤启用状态，`older_than_ms` 表示创建时间早于"现在 - older_than_ms"。
+/// 两个条件都不传时拒绝执行，避免误删全部任务。
+#[tauri::command]
+pub fn scheduler_delete_where(
+    app: AppHandle,
+    enabled: Option<bool>,
+    older_than_ms: Option<i64>,
+) -> Result<i64, String> {
+    if enabled.is_none() && older_than_ms.is_none() {
+        return Err("at least one filter (enabled, older_than_ms) is required".to_string());
+    }
+    if older_than_ms.is_some_and(|ms| ms < 0) {
+        return Err("older_than_ms must be >= 0".to_string());
+    }
+
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let created_before = older_than_ms.map(|ms| now_ms().saturating_sub(ms));
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    let deleted = tx
+        .execute(
+            r#"
+DELETE FROM tasks
+WHERE (?1 IS NULL OR enabled = ?1)
+  AND (?2 IS NULL OR created_at < ?2)
+"#,
+            params![enabled.map(|b| if b { 1 } else { 0 }), created_before],
+        )
+        .map_err(|e| format!("failed to delete tasks: {e}"))?;
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(deleted as i64)
+}
+
+#[tauri::command]
+pub fn scheduler_enable_task(app: AppHandle, id: String, enabled: bool) -> Result<(), String> {
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let existing = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    if enabled && !existing.enabled {
+        check_enabled_task_limit(&conn)?;
+    }
+    let now = now_ms();
+    let next_run = if enabled {
+        compute_next_run(
+            &existing.trigger_type,
+            &existing.trigger_config,
+            existing.metadata.as_deref(),
+            now,
+        )
+    } else {
+        None
+    };
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    tx.execute(
+        r#"
+UPDATE tasks
+SET enabled = ?, next_run = ?, updated_at = ?, disable_after_run = 0
+WHERE id = ?
+"#,
+        params![if enabled { 1 } else { 0 }, next_run, now, id],
+    )
+    .map_err(|e| format!("failed to enable task: {e}"))?;
+    if enabled != existing.enabled {
+        record_audit(
+            &tx,
+            &id,
+            enabled_audit_event(enabled),
+            Some("scheduler_enable_task"),
+        )?;
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(())
+}
+
+/// 去掉已过期的调试循环，返回仍在进行中的任务 id
+fn active_debug_loops(now_ms: i64) -> Vec<String> {
+    let mut loops = DEBUG_LOOPS.lock().unwrap_or_else(|p| p.into_inner());
+    loops.retain(|id, &mut until| {
+        let active = until > now_ms;
+        if !active {
+            log::info!("[Scheduler] debug loop for task {id} ended");
+        }
+        active
+    });
+    loops.keys().cloned().collect()
+}
+
+/// 调试用：接下来 for_seconds 秒内每个 tick 都执行一次该任务（仍受执行配额限制），
+/// 到期后回到原来的排期；for_seconds 为 0 时提前结束。返回截止时间
+#[tauri::command]
+pub fn scheduler_debug_loop(app: AppHandle, id: String, for_seconds: i64) -> Result<i64, String> {
+    if !(0..=DEBUG_LOOP_MAX_SECONDS).contains(&for_seconds) {
+        return Err(format!(
+            "for_seconds must be between 0 and {DEBUG_LOOP_MAX_SECONDS}"
+        ));
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let task = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    let now = now_ms();
+    let mut loops = DEBUG_LOOPS.lock().unwrap_or_else(|p| p.into_inner());
+    if for_seconds == 0 {
+        loops.remove(&id);
+        return Ok(now);
+    }
+    if !task.enabled {
+        return Err("task is disabled".to_string());
+    }
+    // 每个 tick 都会重新发起确认，pending 记录会越积越多
+    if task_options(task.metadata.as_deref()).requires_confirmation {
+        return Err("debug loop is not supported for tasks requiring confirmation".to_string());
+    }
+
+    let until = now + for_seconds * 1000;
+    loops.insert(id.clone(), until);
+    log::info!("[Scheduler] debug loop for task {id} until {until}");
+    Ok(until)
+}
+
+/// 让循环任务按原节奏再跑一次，跑完后自动停用（发 `task_auto_disabled`）；
+/// 之后手动启用/停用会清掉这个标记
+#[tauri::command]
+pub fn scheduler_disable_after_next_run(app: AppHandle, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let task = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    if !task.enabled {
+        return Err("task is disabled".to_string());
+    }
+    conn.execute(
+        "UPDATE tasks SET disable_after_run = 1, updated_at = ? WHERE id = ?",
+        params![now_ms(), id],
+    )
+    .map_err(|e| format!("failed to mark task: {e}"))?;
+    Ok(())
+}
+
+/// 两个互斥任务一次切换：在同一事务里启用一个、停用另一个，不会出现两个同时启用或同时停用的中间状态。
+/// 返回两者切换后的启用状态（task id -> enabled）
+#[tauri::command]
+pub fn scheduler_toggle_exclusive(
+    app: AppHandle,
+    enable_id: String,
+    disable_id: String,
+) -> Result<HashMap<String, bool>, String> {
+    if enable_id == disable_id {
+        return Err("enable_id and disable_id must be different".to_string());
+    }
+
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    let to_enable =
+        get_db_task(&tx, &enable_id)?.ok_or_else(|| "task to enable not found".to_string())?;
+    let to_disable =
+        get_db_task(&tx, &disable_id)?.ok_or_else(|| "task to disable not found".to_string())?;
+    // 两个原本都停用时，切换后启用数净增一个，同样受 max_enabled_tasks 限制
+    if !to_enable.enabled && !to_disable.enabled {
+        check_enabled_task_limit(&tx)?;
+    }
+
+    let now = now_ms();
+    let next_run = compute_next_run(
+        &to_enable.trigger_type,
+        &to_enable.trigger_config,
+        to_enable.metadata.as_deref(),
+        now,
+    );
+    tx.execute(
+        r#"
+UPDATE tasks SET enabled = 1, next_run = ?, updated_at = ?, disable_after_run = 0
+WHERE id = ?
+"#,
+        params![next_run, now, enable_id],
+    )
+    .map_err(|e| format!("failed to enable task: {e}"))?;
+    tx.execute(
+        r#"
+UPDATE tasks SET enabled = 0, next_run = NULL, updated_at = ?, disable_after_run = 0
+WHERE id = ?
+"#,
+        params![now, disable_id],
+    )
+    .map_err(|e| format!("failed to disable task: {e}"))?;
+    for (task, enabled) in [(&to_enable, true), (&to_disable, false)] {
+        if task.enabled != enabled {
+            record_audit(
+                &tx,
+                &task.id,
+                enabled_audit_event(enabled),
+                Some("scheduler_toggle_exclusive"),
+            )?;
+        }
+    }
+
+    let mut states = HashMap::new();
+    for id in [&enable_id, &disable_id] {
+        let task = get_db_task(&tx, id)?.ok_or_else(|| "task not found".to_string())?;
+        states.insert(task.id, task.enabled);
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(states)
+}
+
+#[tauri::command]
+pub fn scheduler_execute_now(app: AppHandle, id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    let task = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    execute_task(&app, &conn, &task)?;
+    Ok(())
+}
+
+/// scheduler_test_action 的返回值，只回给前端、不写执行记录
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TestActionResult {
+    pub status: String,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+/// 任务表单上的"测试"按钮：按给定配置真正执行一次 action，但不建任务、不写执行记录；
+/// 用户主动测试，勿扰模式不拦截
+#[tauri::command]
+pub fn scheduler_test_action(
+    app: AppHandle,
+    action: ActionConfig,
+) -> Result<TestActionResult, String> {
+    let (action_type, action_config) = action.to_db()?;
+    let outcome = perform_action(&app, &action_type, &action_config, None, false, false);
+    Ok(TestActionResult {
+        status: outcome.status,
+        result: outcome
+            .result_json
+            .as_deref()
+            .and_then(|r| serde_json::from_str(r).ok()),
+        error: outcome.error,
+    })
+}
+
+/// 按某次历史执行当时的 action 配置快照再跑一次（即使任务之后改过），结果记为该任务的一条新执行；
+/// 不更新 last_run/next_run，不影响排期。快照功能加入之前的执行记录无法重放
+#[tauri::command]
+pub fn scheduler_replay_execution(app: AppHandle, exec_id: String) -> Result<(), String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let (task_id, snapshot): (String, Option<String>) = conn
+        .query_row(
+            "SELECT task_id, config_snapshot FROM task_executions WHERE id = ?",
+            params![exec_id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("failed to load execution: {e}"))?
+        .ok_or_else(|| "execution not found".to_string())?;
+    let snapshot = snapshot.ok_or_else(|| "execution has no config snapshot".to_string())?;
+    let snapshot: serde_json::Value =
+        serde_json::from_str(&snapshot).map_err(|e| format!("invalid config snapshot: {e}"))?;
+    let (Some(action_type), Some(action_config)) = (
+        snapshot.get("actionType").and_then(|v| v.as_str()),
+        snapshot.get("actionConfig").and_then(|v| v.as_str()),
+    ) else {
+        return Err("invalid config snapshot".to_string());
+    };
+
+    let disabled = load_disabled_action_types(&conn)?;
+    if let Some(blocked) = effective_action_types(action_type, action_config)
+        .into_iter()
+        .find(|t| disabled.contains(t))
+    {
+        return Err(format!("action type {blocked} is disabled"));
+    }
+
+    let start_ms = now_ms();
+    let replay_id = Uuid::new_v4().to_string();
+    conn.execute(
+        r#"
+INSERT INTO task_executions (id, task_id, status, started_at, config_snapshot)
+VALUES (?, ?, 'running', ?, ?)
+"#,
+        params![
+            replay_id,
+            task_id,
+            start_ms,
+            action_snapshot(action_type, action_config)
+        ],
+    )
+    .map_err(|e| format!("failed to insert execution: {e}"))?;
+
+    let outcome = {
+        let _in_flight = InFlightGuard::enter(&task_id);
+        perform_action(
+            &app,
+            action_type,
+            action_config,
+            Some(&replay_id),
+            false,
+            false,
+        )
+    };
+
+    let end_ms = now_ms();
+    let (stored_result, result_path) = match outcome.result_json.as_deref() {
+        Some(full) => {
+            let max_bytes = load_max_result_bytes(&conn)?;
+            let (stored, path) = spill_result(&app, &replay_id, full, max_bytes)?;
+            (Some(stored), path)
+        }
+        None => (None, None),
+    };
+    conn.execute(
+        r#"
+UPDATE task_executions
+SET status = ?, completed_at = ?, result = ?, error = ?, duration = ?, result_path = ?
+WHERE id = ?
+"#,
+        params![
+            outcome.status,
+            end_ms,
+            stored_result,
+            outcome.error,
+            end_ms.saturating_sub(start_ms),
+            result_path,
+            replay_id
+        ],
+    )
+    .map_err(|e| format!("failed to update execution: {e}"))?;
+
+    log::info!("[Scheduler] replayed execution {exec_id} of task {task_id} as {replay_id}");
+    Ok(())
+}
+
+/// 只改这一次的 next_run；执行完后按触发器配置重新排期，恢复原来的节奏
+#[tauri::command]
+pub fn scheduler_set_next_run(app: AppHandle, id: String, next_run_ms: i64) -> Result<(), String> {
+    let now = now_ms();
+    if next_run_ms <= now {
+        return Err("next_run_ms must be in the future".to_string());
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let task = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    if !task.enabled {
+        return Err("task is disabled".to_string());
+    }
+    conn.execute(
+        "UPDATE tasks SET next_run = ?, updated_at = ? WHERE id = ?",
+        params![next_run_ms, now, id],
+    )
+    .map_err(|e| format!("failed to set next_run: {e}"))?;
+    Ok(())
+}
+
+/// 把 cron 触发器（含组合触发器的子项）的 5 段表达式改写成 6 段，返回是否改动
+fn normalize_cron_node(config: &mut serde_json::Value) -> Result<bool, String> {
+    let Some(obj) = config.as_object_mut() else {
+        return Ok(false);
+    };
+    match obj.get("type").and_then(|t| t.as_str()) {
+        Some("cron") => {}
+        Some("composite") => {
+            let mut changed = false;
+            if let Some(children) = obj.get_mut("children").and_then(|c| c.as_array_mut()) {
+                for child in children {
+                    changed |= normalize_cron_node(child)?;
+                }
+            }
+            return Ok(changed);
+        }
+        _ => return Ok(false),
+    }
+
+    let Some(expression) = obj.get("expression").and_then(|v| v.as_str()) else {
+        return Err("cron trigger has no expression".to_string());
+    };
+    if expression.split_whitespace().count() != 5 {
+        return Ok(false);
+    }
+    let explicit = format!(
+        "0 {}",
+        expression.split_whitespace().collect::<Vec<_>>().join(" ")
+    );
+    parse_cron_schedule(&explicit)?;
+    obj.insert(
+        "expression".to_string(),
+        serde_json::Value::String(explicit),
+    );
+    Ok(true)
+}
+
+/// 把库里所有 5 段 cron 表达式（含组合触发器里的 cron 子项）改写成显式带秒字段的 6 段写法
+/// （`0 {expr}`），调度结果不变；已是 6 段的跳过。逐条校验后在一个事务里写入，返回改写的任务数
+#[tauri::command]
+pub fn scheduler_normalize_cron_expressions(app: AppHandle) -> Result<i64, String> {
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    let rows: Vec<(String, String)> = {
+        let mut stmt = tx
+            .prepare(
+                "SELECT id, trigger_config FROM tasks WHERE trigger_type IN ('cron', 'composite')",
+            )
+            .map_err(|e| format!("failed to prepare cron task query: {e}"))?;
+        let rows = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))
+            .map_err(|e| format!("failed to query cron tasks: {e}"))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("failed to map cron task: {e}"))?
+    };
+
+    let now = now_ms();
+    let mut changed = 0;
+    for (id, raw) in rows {
+        let mut config: serde_json::Value = serde_json::from_str(&raw)
+            .map_err(|e| format!("invalid trigger config for task {id}: {e}"))?;
+        if !normalize_cron_node(&mut config).map_err(|e| format!("task {id}: {e}"))? {
+            continue;
+        }
+        tx.execute(
+            "UPDATE tasks SET trigger_config = ?, updated_at = ? WHERE id = ?",
+            params![config.to_string(), now, id],
+        )
+        .map_err(|e| format!("failed to update task {id}: {e}"))?;
+        changed += 1;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+    Ok(changed)
+}
+
+/// 把 timezone 写进按墙上时间排期的触发器配置（含组合触发器的子项），返回是否改动
+fn inject_timezone(config: &mut serde_json::Value, tz: &str) -> bool {
+    let Some(obj) = config.as_object_mut() else {
+        return false;
+    };
+    match obj.get("type").and_then(|t| t.as_str()) {
+        Some("cron" | "weekly" | "lunar") => {}
+        Some("interval")
+            if obj.get("intervalMode").and_then(|m| m.as_str()) == Some("wallclock") => {}
+        Some("composite") => {
+            let mut changed = false;
+            if let Some(children) = obj.get_mut("children").and_then(|c| c.as_array_mut()) {
+                for child in children {
+                    changed |= inject_timezone(child, tz);
+                }
+            }
+            return changed;
+        }
+        _ => return false,
+    }
+    obj.insert(
+        "timezone".to_string(),
+        serde_json::Value::String(tz.to_string()),
+    );
+    true
+}
+
+/// 用户搬到新时区后批量改写所有墙上时间类触发器的 timezone；
+/// recompute 为 true 时顺带重排已启用任务的 next_run。返回改动的任务数
+#[tauri::command]
+pub fn scheduler_set_timezone_all(
+    app: AppHandle,
+    tz: String,
+    recompute: bool,
+) -> Result<i64, String> {
+    let tz = tz.trim().to_string();
+    tz.parse::<Tz>()
+        .map_err(|_| format!("unknown timezone: {tz}"))?;
+
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    let tasks = {
+        let mut stmt = tx
+            .prepare("SELECT id, trigger_type, trigger_config, enabled, metadata FROM tasks")
+            .map_err(|e| format!("failed to prepare tasks: {e}"))?;
+        let rows = stmt
+            .query_map([], |r| {
+                Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get::<_, String>(2)?,
+                    r.get::<_, i64>(3)? == 1,
+                    r.get::<_, Option<String>>(4)?,
+                ))
+            })
+            .map_err(|e| format!("failed to query tasks: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("task map error: {e}"))?
+    };
+
+    let now = now_ms();
+    let mut updated = 0;
+    for (id, trigger_type, trigger_config, enabled, metadata) in tasks {
+        // 旧数据的 config 可能没有 type 字段，按列上的类型补上再判断
+        let Ok(mut config) = serde_json::from_str::<serde_json::Value>(&trigger_config) else {
+            continue;
+        };
+        if let Some(obj) = config.as_object_mut() {
+            obj.entry("type")
+                .or_insert_with(|| serde_json::Value::String(trigger_type.clone()));
+        }
+        if !inject_timezone(&mut config, &tz) {
+            continue;
+        }
+
+        let new_config = config.to_string();
+        if recompute && enabled {
+            let next_run = compute_next_run(&trigger_type, &new_config, metadata.as_deref(), now);
+            tx.execute(
+                "UPDATE tasks SET trigger_config = ?, next_run = ?, updated_at = ? WHERE id = ?",
+                params![new_config, next_run, now, id],
+            )
+        } else {
+            tx.execute(
+                "UPDATE tasks SET trigger_config = ?, updated_at = ? WHERE id = ?",
+                params![new_config, now, id],
+            )
+        }
+        .map_err(|e| format!("failed to update trigger timezone: {e}"))?;
+        updated += 1;
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(updated)
+}
+
+/// 把已到期（next_run <= 现在）的任务整体推后 minutes 分钟，不改启用状态；
+/// action_types 缺省只处理 notification，返回推后的任务数
+#[tauri::command]
+pub fn scheduler_snooze_all(
+    app: AppHandle,
+    minutes: i64,
+    action_types: Option<Vec<String>>,
+) -> Result<i64, String> {
+    if minutes <= 0 {
+        return Err("minutes must be > 0".to_string());
+    }
+    let action_types = action_types.unwrap_or_else(|| vec!["notification".to_string()]);
+
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let now = now_ms();
+    let snoozed_until = now + minutes * 60_000;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    let mut snoozed = 0;
+    for action_type in &action_types {
+        snoozed += tx
+            .execute(
+                r#"
+UPDATE tasks
+SET next_run = ?, updated_at = ?
+WHERE enabled = 1 AND next_run IS NOT NULL AND next_run <= ? AND action_type = ?
+"#,
+                params![snoozed_until, now, now, action_type],
+            )
+            .map_err(|e| format!("failed to snooze tasks: {e}"))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(snoozed as i64)
+}
+
+/// 一次查询拿到每个任务最近的一条执行记录；没有历史的任务不会出现在结果里
+#[tauri::command]
+pub fn scheduler_get_latest_executions(
+    app: AppHandle,
+    task_ids: Vec<String>,
+) -> Result<HashMap<String, ApiTaskExecution>, String> {
+    if task_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let placeholders = vec!["?"; task_ids.len()].join(", ");
+    let sql = format!(
+        r#"
+SELECT {EXECUTION_COLUMNS}
+FROM (
+  SELECT *, ROW_NUMBER() OVER (PARTITION BY task_id ORDER BY started_at DESC) AS rn
+  FROM task_executions
+  WHERE task_id IN ({placeholders})
+)
+WHERE rn = 1
+"#
+    );
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(|e| format!("failed to prepare latest executions: {e}"))?;
+
+    let rows = stmt
+        .query_map(params_from_iter(task_ids.iter()), execution_from_row)
+        .map_err(|e| format!("failed to query latest executions: {e}"))?;
+
+    let mut out = HashMap::new();
+    for row in rows {
+        let execution = row.map_err(|e| format!("execution map error: {e}"))?;
+        out.insert(execution.task_id.clone(), execution);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskReliability {
+    pub task_id: String,
+    pub total: i64,
+    pub failed: i64,
+    /// 0.0 ~ 1.0；suppressed（免打扰期间执行）算成功
+    pub success_rate: f64,
+    pub last_status: String,
+}
+
+/// 最近 window_days 天内每个任务的执行成功率，供列表里的健康状态小圆点使用；
+/// 只统计真正跑完的记录，窗口内没有执行的任务不返回
+#[tauri::command]
+pub fn scheduler_get_reliability(
+    app: AppHandle,
+    window_days: i64,
+) -> Result<Vec<TaskReliability>, String> {
+    if window_days <= 0 {
+        return Err("window_days must be > 0".to_string());
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT task_id, COUNT(*), SUM(status = 'failed'), MAX(CASE WHEN rn = 1 THEN status END)
+FROM (
+  SELECT task_id, status, ROW_NUMBER() OVER (PARTITION BY task_id ORDER BY started_at DESC) AS rn
+  FROM task_executions
+  WHERE started_at >= ? AND status IN ('success', 'failed', 'suppressed')
+)
+GROUP BY task_id
+"#,
+        )
+        .map_err(|e| format!("failed to prepare reliability: {e}"))?;
+
+    let since = now_ms() - window_days * 86_400_000;
+    let rows = stmt
+        .query_map(params![since], |r| {
+            let total: i64 = r.get(1)?;
+            let failed: i64 = r.get(2)?;
+            Ok(TaskReliability {
+                task_id: r.get(0)?,
+                total,
+                failed,
+                success_rate: (total - failed) as f64 / total as f64,
+                last_status: r.get(3)?,
+            })
+        })
+        .map_err(|e| format!("failed to query reliability: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row.map_err(|e| format!("reliability map error: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DispatchStats {
+    pub samples: i64,
+    pub avg_ms: f64,
+    pub p95_ms: i64,
+}
+
+/// action 分发耗时（配置解析 + emit）的平均值和 p95，只统计记录了 dispatch_ms 的执行
+#[tauri::command]
+pub fn scheduler_get_dispatch_stats(app: AppHandle) -> Result<DispatchStats, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT dispatch_ms FROM task_executions WHERE dispatch_ms IS NOT NULL ORDER BY dispatch_ms",
+        )
+        .map_err(|e| format!("failed to prepare dispatch stats: {e}"))?;
+    let values = stmt
+        .query_map([], |r| r.get::<_, i64>(0))
+        .map_err(|e| format!("failed to query dispatch stats: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("dispatch stats map error: {e}"))?;
+
+    if values.is_empty() {
+        return Ok(DispatchStats {
+            samples: 0,
+            avg_ms: 0.0,
+            p95_ms: 0,
+        });
+    }
+
+    // nearest-rank：第 ceil(0.95 * n) 个值
+    let n = values.len();
+    let rank = (n * 95).div_ceil(100).max(1);
+    Ok(DispatchStats {
+        samples: n as i64,
+        avg_ms: values.iter().sum::<i64>() as f64 / n as f64,
+        p95_ms: values[rank - 1],
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleTask {
+    pub task_id: String,
+    pub name: String,
+    pub last_run: Option<i64>,
+    pub expected_next_run: Option<i64>,
+}
+
+/// 已启用但从未执行过、或超过 threshold_days 没执行过的任务，最久没跑的排在前面（从未执行的最先）
+#[tauri::command]
+pub fn scheduler_get_stale_tasks(
+    app: AppHandle,
+    threshold_days: i64,
+) -> Result<Vec<StaleTask>, String> {
+    if threshold_days <= 0 {
+        return Err("threshold_days must be > 0".to_string());
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT id, name, last_run, next_run
+FROM tasks
+WHERE enabled = 1 AND (last_run IS NULL OR last_run < ?)
+ORDER BY last_run ASC, created_at ASC
+"#,
+        )
+        .map_err(|e| format!("failed to prepare stale tasks: {e}"))?;
+
+    let cutoff = now_ms() - threshold_days * 86_400_000;
+    let rows = stmt
+        .query_map(params![cutoff], |r| {
+            Ok(StaleTask {
+                task_id: r.get(0)?,
+                name: r.get(1)?,
+                last_run: r.get(2)?,
+                expected_next_run: r.get(3)?,
+            })
+        })
+        .map_err(|e| format!("failed to query stale tasks: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row.map_err(|e| format!("stale task map error: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiAuditEntry {
+    pub id: String,
+    pub task_id: String,
+    pub event: String,
+    pub detail: Option<String>,
+    pub at: i64,
+}
+
+#[tauri::command]
+pub fn scheduler_get_audit(
+    app: AppHandle,
+    task_id: String,
+    limit: Option<i64>,
+) -> Result<Vec<ApiAuditEntry>, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+
+    let mut stmt = conn
+        .prepare(
+            r#"
+SELECT id, task_id, event, detail, at
+FROM audit_log
+WHERE task_id = ?
+ORDER BY at DESC
+LIMIT ?
+"#,
+        )
+        .map_err(|e| format!("failed to prepare list audit: {e}"))?;
+
+    let rows = stmt
+        .query_map(params![task_id, limit], |r| {
+            Ok(ApiAuditEntry {
+                id: r.get(0)?,
+                task_id: r.get(1)?,
+                event: r.get(2)?,
+                detail: r.get(3)?,
+                at: r.get(4)?,
+            })
+        })
+        .map_err(|e| format!("failed to query audit: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row.map_err(|e| format!("audit map error: {e}"))?);
+    }
+    Ok(out)
+}
+
+#[tauri::command]
+pub fn scheduler_reassign_executions(
+    app: AppHandle,
+    from_task_id: String,
+    to_task_id: String,
+) -> Result<i64, String> {
+    if from_task_id == to_task_id {
+        return Err("source and target task must be different".to_string());
+    }
+
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    // 先校验两端都存在，避免把历史挂到不存在的任务上
+    get_db_task(&tx, &from_task_id)?.ok_or_else(|| "source task not found".to_string())?;
+    get_db_task(&tx, &to_task_id)?.ok_or_else(|| "target task not found".to_string())?;
+
+    let moved = tx
+        .execute(
+            "UPDATE task_executions SET task_id = ? WHERE task_id = ?",
+            params![to_task_id, from_task_id],
+        )
+        .map_err(|e| format!("failed to reassign executions: {e}"))?;
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    Ok(moved as i64)
+}
+
+/// 用户手动清空某个任务的执行历史，任务本身和 next_run 不动；reset_last_run 为 true 时顺带清掉 last_run。
+/// 正在运行和等待确认的记录保留，否则它们结束时找不到对应的行
+#[tauri::command]
+pub fn scheduler_clear_executions(
+    app: AppHandle,
+    task_id: String,
+    reset_last_run: Option<bool>,
+) -> Result<i64, String> {
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    get_db_task(&tx, &task_id)?.ok_or_else(|| "task not found".to_string())?;
+
+    let result_paths = {
+        let mut stmt = tx
+            .prepare(
+                r#"
+SELECT result_path FROM task_executions
+WHERE task_id = ? AND result_path IS NOT NULL
+  AND status NOT IN ('running', 'pending_confirmation')
+"#,
+            )
+            .map_err(|e| format!("failed to prepare result paths: {e}"))?;
+        let rows = stmt
+            .query_map(params![task_id], |r| r.get::<_, String>(0))
+            .map_err(|e| format!("failed to query result paths: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("result path map error: {e}"))?
+    };
+
+    let cleared = tx
+        .execute(
+            r#"
+DELETE FROM task_executions
+WHERE task_id = ? AND status NOT IN ('running', 'pending_confirmation')
+"#,
+            params![task_id],
+        )
+        .map_err(|e| format!("failed to clear executions: {e}"))?;
+    if reset_last_run.unwrap_or(false) {
+        tx.execute(
+            "UPDATE tasks SET last_run = NULL, updated_at = ? WHERE id = ?",
+            params![now_ms(), task_id],
+        )
+        .map_err(|e| format!("failed to reset last_run: {e}"))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    // 行删掉之后再清理溢出的完整结果文件，删不掉也不影响结果
+    for path in result_paths {
+        let _ = std::fs::remove_file(path);
+    }
+
+    Ok(cleared as i64)
+}
+
+/// 开启 foreign_keys 之前留下的、task_id 已不存在的执行记录数
+#[tauri::command]
+pub fn scheduler_find_orphaned_executions(app: AppHandle) -> Result<i64, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    conn.query_row(
+        r#"
+SELECT COUNT(*)
+FROM task_executions e
+LEFT JOIN tasks t ON t.id = e.task_id
+WHERE t.id IS NULL
+"#,
+        [],
+        |r| r.get(0),
+    )
+    .map_err(|e| format!("failed to count orphaned executions: {e}"))
+}
+
+/// 一次性清理孤立的执行记录（连同溢出的结果文件），返回删除条数
+#[tauri::command]
+pub fn scheduler_purge_orphaned_executions(app: AppHandle) -> Result<i64, String> {
+    let mut conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    let result_paths = {
+        let mut stmt = tx
+            .prepare(
+                r#"
+SELECT result_path FROM task_executions
+WHERE result_path IS NOT NULL AND task_id NOT IN (SELECT id FROM tasks)
+"#,
+            )
+            .map_err(|e| format!("failed to prepare result paths: {e}"))?;
+        let rows = stmt
+            .query_map([], |r| r.get::<_, String>(0))
+            .map_err(|e| format!("failed to query result paths: {e}"))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("result path map error: {e}"))?
+    };
+
+    let purged = tx
+        .execute(
+            "DELETE FROM task_executions WHERE task_id NOT IN (SELECT id FROM tasks)",
+            [],
+        )
+        .map_err(|e| format!("failed to purge orphaned executions: {e}"))?;
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
+
+    for path in result_paths {
+        let _ = std::fs::remove_file(path);
+    }
+
+    if purged > 0 {
+        log::info!("[Scheduler] purged {purged} orphaned executions");
+    }
+    Ok(purged as i64)
+}
+
+/// 处理一条 pending_confirmation 执行：批准则就地执行并重排，拒绝则标为 declined 并推进 next_run
+#[tauri::command]
+pub fn scheduler_confirm_execution(
+    app: AppHandle,
+    runner: State<'_, SchedulerRunner>,
+    exec_id: String,
+    approve: bool,
+) -> Result<(), String> {
+    // 与 tick 互斥：状态切换到 next_run 推进之间，tick 不能再为同一任务发起确认
+    let _paused = runner.pause_ticks();
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let task_id: String = conn
+        .query_row(
+            "SELECT task_id FROM task_executions WHERE id = ? AND status = 'pending_confirmation'",
+            params![exec_id],
+            |r| r.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("failed to query execution: {e}"))?
+        .ok_or_else(|| "no pending confirmation for this execution".to_string())?;
+
+    let now = now_ms();
+    if !approve {
+        return finish_unconfirmed(&conn, &exec_id, &task_id, "declined", now);
+    }
+
+    let task = get_db_task(&conn, &task_id)?.ok_or_else(|| "task not found".to_string())?;
+    conn.execute(
+        "UPDATE task_executions SET status = 'running', started_at = ? WHERE id = ?",
+        params![now, exec_id],
+    )
+    .map_err(|e| format!("failed to start execution: {e}"))?;
+    run_execution(&app, &conn, &task, exec_id, now)
+}
+
+/// 异步 action（agent_task / workflow）在前端跑完后回报：用真实耗时覆盖分发时记下的 duration，
+/// 写入最终状态和结果，再做慢执行检查。status 为 "success" | "failed"
+#[tauri::command]
+pub fn scheduler_complete_execution(
+    app: AppHandle,
+    exec_id: String,
+    status: String,
+    result: Option<String>,
+    error: Option<String>,
+) -> Result<(), String> {
+    if !matches!(status.as_str(), "success" | "failed") {
+        return Err(format!("unknown completion status: {status}"));
+    }
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let (task_id, started_at): (String, i64) = conn
+        .query_row(
+            "SELECT task_id, started_at FROM task_executions WHERE id = ? AND status = 'success'",
+            params![exec_id],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("failed to query execution: {e}"))?
+        .ok_or_else(|| "no dispatched execution with this id".to_string())?;
+    let task = get_db_task(&conn, &task_id)?.ok_or_else(|| "task not found".to_string())?;
+    if !is_async_action(&task.action_type) {
+        return Err(format!(
+            "{} actions complete synchronously and cannot be reported",
+            task.action_type
+        ));
+    }
+
+    let end_ms = now_ms();
+    let duration = end_ms.saturating_sub(started_at);
+    // 前端回报的结果同样受 max_result_bytes 约束，超出部分落盘
+    let (stored_result, result_path) = match result.as_deref() {
+        Some(full) => {
+            let max_bytes = load_max_result_bytes(&conn)?;
+            let (stored, path) = spill_result(&app, &exec_id, full, max_bytes)?;
+            (Some(stored), path)
+        }
+        None => (None, None),
+    };
+    conn.execute(
+        r#"
+UPDATE task_executions
+SET status = ?, completed_at = ?, duration = ?, result = COALESCE(?, result),
+    result_path = COALESCE(?, result_path), error = ?
+WHERE id = ?
+"#,
+        params![
+            status,
+            end_ms,
+            duration,
+            stored_result,
+            result_path,
+            error,
+            exec_id
+        ],
+    )
+    .map_err(|e| format!("failed to complete execution: {e}"))?;
+
+    check_slow_execution(&app, &conn, &task, &exec_id, duration)
+}
+
+/// 前端在电源状态变化时上报；只更新内存里的标志，下一轮 tick 生效
+#[tauri::command]
+pub fn scheduler_report_power_state(on_battery: bool) {
+    ON_BATTERY.store(on_battery, Ordering::Relaxed);
+}
+
+/// 订阅单个任务的 task_started / task_completed / task_failed。channel 由前端创建并传入，
+/// 返回其 id，取消订阅时带回
+#[tauri::command]
+pub fn scheduler_subscribe_task(
+    runner: State<'_, SchedulerRunner>,
+    task_id: String,
+    on_event: Channel<serde_json::Value>,
+) -> u32 {
+    let id = on_event.id();
+    runner
+        .state
+        .task_subscribers
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .entry(task_id)
+        .or_default()
+        .push(on_event);
+    id
+}
+
+/// 返回是否找到并移除了该订阅
+#[tauri::command]
+pub fn scheduler_unsubscribe_task(
+    runner: State<'_, SchedulerRunner>,
+    task_id: String,
+    channel_id: u32,
+) -> bool {
+    let mut subscribers = runner
+        .state
+        .task_subscribers
+        .lock()
+        .unwrap_or_else(|p| p.into_inner());
+    let Some(channels) = subscribers.get_mut(&task_id) else {
+        return false;
+    };
+    let before = channels.len();
+    channels.retain(|channel| channel.id() != channel_id);
+    let removed = channels.len() != before;
+    if channels.is_empty() {
+        subscribers.remove(&task_id);
+    }
+    removed
+}
+
+/// 记录前端发生的事件，供组合触发器里的 event 条件判断
+#[tauri::command]
+pub fn scheduler_report_event(runner: State<'_, SchedulerRunner>, event_name: String) {
+    runner.state.record_event(event_name, now_ms());
+}
+
+/// 前端上报当前空闲时长。空闲越过阈值时触发对应的 idle 任务，每段空闲只触发一次；
+/// 空闲时长回落到阈值以下（用户回来了）后清除标记，下次空闲可再次触发。返回本次触发的任务数。
+#[tauri::command]
+pub fn scheduler_report_idle(app: AppHandle, idle_seconds: i64) -> Result<i64, String> {
+    let now = now_ms();
+    runtime_state(&app).record_idle(idle_seconds, now);
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut fired = 0;
+    for task in claim_idle_tasks(&conn, idle_seconds, now)? {
+        if let Err(err) = execute_task(&app, &conn, &task) {
+            log::error!("[Scheduler] idle task {} failed: {err}", task.id);
+            continue;
+        }
+        fired += 1;
+    }
+
+    Ok(fired)
+}
+
+/// 按这次上报的空闲时长更新各 idle 任务的触发标记，返回这次应当触发的任务。
+/// 先落标记再由调用方执行，避免执行出错时下一次上报重复触发
+fn claim_idle_tasks(
+    conn: &Connection,
+    idle_seconds: i64,
+    now_ms: i64,
+) -> Result<Vec<DbTaskRow>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+SELECT {TASK_COLUMNS}, last_idle_fired
+FROM tasks
+WHERE enabled = 1 AND trigger_type = 'idle'
+"#
+        ))
+        .map_err(|e| format!("failed to prepare idle task query: {e}"))?;
+
+    let rows = stmt
+        .query_map([], |r| {
+            Ok((task_from_row(r)?, r.get::<_, Option<i64>>(14)?))
+        })
+        .map_err(|e| format!("failed to query idle tasks: {e}"))?;
+
+    let mut tasks = Vec::new();
+    for row in rows {
+        tasks.push(row.map_err(|e| format!("idle task map error: {e}"))?);
+    }
+    drop(stmt);
+
+    let mut claimed = Vec::new();
+    for (task, last_idle_fired) in tasks {
+        let Ok(cfg) = serde_json::from_str::<IdleTriggerConfig>(&task.trigger_config) else {
+            continue;
+        };
+
+        if idle_seconds < cfg.idle_seconds {
+            if last_idle_fired.is_some() {
+                conn.execute(
+                    "UPDATE tasks SET last_idle_fired = NULL WHERE id = ?",
+                    params![task.id],
+                )
+                .map_err(|e| format!("failed to reset idle marker: {e}"))?;
+            }
+            continue;
+        }
+        if last_idle_fired.is_some() {
+            continue;
+        }
+
+        conn.execute(
+            "UPDATE tasks SET last_idle_fired = ? WHERE id = ?",
+            params![now_ms, task.id],
+        )
+        .map_err(|e| format!("failed to set idle marker: {e}"))?;
+        claimed.push(task);
+    }
+
+    Ok(claimed)
+}
+
+#[tauri::command]
+pub fn scheduler_get_executions(
+    app: AppHandle,
+    task_id: String,
+    limit: Option<i64>,
+) -> Result<Vec<ApiTaskExecution>, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+
+    let mut stmt = conn
+        .prepare(&format!(
+            r#"
+SELECT {EXECUTION_COLUMNS}
+FROM task_executions
+WHERE task_id = ?
+ORDER BY started_at DESC
+LIMIT ?
+"#
+        ))
+        .map_err(|e| format!("failed to prepare list executions: {e}"))?;
+
+    let rows = stmt
+        .query_map(params![task_id, limit], execution_from_row)
+        .map_err(|e| format!("failed to query executions: {e}"))?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        out.push(row.map_err(|e| format!("execution map error: {e}"))?);
+    }
+    Ok(out)
+}
+
+/// 执行的完整结果：被截断过的从文件读取，否则直接返回数据库里的 result
+#[tauri::command]
+pub fn scheduler_get_execution_result(app: AppHandle, exec_id: String) -> Result<String, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let (result, result_path) = conn
+        .query_row(
+            "SELECT result, result_path FROM task_executions WHERE id = ?",
+            params![exec_id],
+            |r| {
+                Ok((
+                    r.get::<_, Option<String>>(0)?,
+                    r.get::<_, Option<String>>(1)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| format!("failed to query execution: {e}"))?
+        .ok_or_else(|| "execution not found".to_string())?;
+
+    match (result_path, result) {
+        (Some(path), _) => {
+            std::fs::read_to_string(&path).map_err(|e| format!("failed to read result file: {e}"))
+        }
+        (None, Some(result)) => Ok(result),
+        (None, None) => Err("execution has no result".to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn scheduler_set_dnd(
+    app: AppHandle,
+    enabled: bool,
+    until_ms: Option<i64>,
+) -> Result<DndState, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    if !enabled {
+        delete_setting(&conn, DND_SETTING_KEY)?;
+        return Ok(DndState {
+            enabled: false,
+            until_ms: None,
+        });
+    }
+
+    if until_ms.is_some_and(|until| until <= now_ms()) {
+        return Err("dnd until_ms must be in the future".to_string());
+    }
+
+    let state = DndState { enabled, until_ms };
+    let raw = serde_json::to_string(&state).map_err(|e| format!("failed to encode dnd: {e}"))?;
+    set_setting(&conn, DND_SETTING_KEY, &raw)?;
+    Ok(state)
+}
+
+#[tauri::command]
+pub fn scheduler_set_log_level(app: AppHandle, level: String) -> Result<(), String> {
+    let filter = crate::logging::parse_level(&level)?;
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    set_setting(
+        &conn,
+        LOG_LEVEL_SETTING_KEY,
+        &filter.to_string().to_lowercase(),
+    )?;
+    log::set_max_level(filter);
+    Ok(())
+}
+
+/// 把调度器时钟拨到 ms（<= 0 恢复系统时间），用于手动验证排期；仅 `mock-clock` feature 构建可用
+#[tauri::command]
+pub fn scheduler_set_mock_time(ms: i64) -> Result<(), String> {
+    #[cfg(feature = "mock-clock")]
+    {
+        MOCK_CLOCK.set(ms);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "mock-clock"))]
+    {
+        let _ = ms;
+        Err("mock time requires a build with the `mock-clock` feature".to_string())
+    }
+}
+
+#[tauri::command]
+pub fn scheduler_get_log_path(app: AppHandle) -> Result<String, String> {
+    crate::logging::log_path(&app).map(|p| p.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub fn scheduler_get_dnd(app: AppHandle) -> Result<DndState, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    load_dnd(&conn, now_ms())
+}
+
+#[tauri::command]
+pub fn scheduler_get_rate_limits(app: AppHandle) -> Result<RateLimits, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    load_rate_limits(&conn)
+}
+
+#[tauri::command]
+pub fn scheduler_set_rate_limits(
+    app: AppHandle,
+    min_interval_seconds: Option<i64>,
+    max_executions_per_minute: Option<i64>,
+) -> Result<RateLimits, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    let mut limits = load_rate_limits(&conn)?;
+    if let Some(seconds) = min_interval_seconds {
+        limits.min_interval_seconds = seconds;
+    }
+    if let Some(max) = max_executions_per_minute {
+        limits.max_executions_per_minute = max;
+    }
+    validate_rate_limits(&limits)?;
+
+    let raw =
+        serde_json::to_string(&limits).map_err(|e| format!("failed to encode rate limits: {e}"))?;
+    set_setting(&conn, RATE_LIMITS_SETTING_KEY, &raw)?;
+    MIN_INTERVAL_SECONDS.store(limits.min_interval_seconds, Ordering::Relaxed);
+    Ok(limits)
+}
+
+fn validate_rate_limits(limits: &RateLimits) -> Result<(), String> {
+    if limits.min_interval_seconds < 1 {
+        return Err("min_interval_seconds must be >= 1".to_string());
+    }
+    if limits.max_executions_per_minute < 0 {
+        return Err("max_executions_per_minute must be >= 0".to_string());
+    }
+    Ok(())
+}
+
+const NOTIFICATION_RATE_LIMIT_SETTING_KEY: &str = "notification_rate_limit";
+
+/// 全局通知令牌桶：每分钟补充 per_minute 个，最多攒 burst 个；per_minute 为 0 表示不限制
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationRateLimit {
+    pub per_minute: f64,
+    pub burst: i64,
+}
+
+#[derive(Default)]
+struct NotificationBucket {
+    limit: NotificationRateLimit,
+    tokens: f64,
+    last_ms: i64,
+}
+
+impl NotificationBucket {
+    /// 限额变化时桶重新装满
+    fn apply(&mut self, limit: NotificationRateLimit, now_ms: i64) {
+        if self.limit != limit {
+            self.limit = limit;
+            self.tokens = limit.burst as f64;
+            self.last_ms = now_ms;
+        }
+    }
+
+    /// 取一个通知令牌；桶空时返回 false
+    fn take(&mut self, now_ms: i64) -> bool {
+        let limit = self.limit;
+        if limit.per_minute <= 0.0 {
+            return true;
+        }
+        let elapsed = (now_ms - self.last_ms).max(0) as f64;
+        self.tokens = (self.tokens + elapsed / 60_000.0 * limit.per_minute).min(limit.burst as f64);
+        self.last_ms = now_ms;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// 读取通知限额并同步到运行时的令牌桶
+fn load_notification_rate_limit(
+    app: &AppHandle,
+    conn: &Connection,
+) -> Result<NotificationRateLimit, String> {
+    let limit = get_setting(conn, NOTIFICATION_RATE_LIMIT_SETTING_KEY)?
+        .and_then(|raw| serde_json::from_str::<NotificationRateLimit>(&raw).ok())
+        .unwrap_or_default();
+    apply_notification_rate_limit(app, limit);
+    Ok(limit)
+}
+
+fn apply_notification_rate_limit(app: &AppHandle, limit: NotificationRateLimit) {
+    runtime_state(app)
+        .notification_bucket
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .apply(limit, now_ms());
+}
+
+fn take_notification_token(app: &AppHandle, now_ms: i64) -> bool {
+    runtime_state(app)
+        .notification_bucket
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .take(now_ms)
+}
+
+fn validate_notification_rate_limit(limit: &NotificationRateLimit) -> Result<(), String> {
+    if !limit.per_minute.is_finite() || limit.per_minute < 0.0 {
+        return Err("per_minute must be >= 0".to_string());
+    }
+    if limit.per_minute > 0.0 && limit.burst < 1 {
+        return Err("burst must be >= 1".to_string());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn scheduler_get_notification_rate_limit(
+    app: AppHandle,
+) -> Result<NotificationRateLimit, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    load_notification_rate_limit(&app, &conn)
+}
+
+/// 所有任务共用的通知上限，例如 10 分钟最多 5 条：per_minute = 0.5, burst = 5；
+/// 超出的执行记为 throttled，不弹通知，照常排下一次
+#[tauri::command]
+pub fn scheduler_set_notification_rate_limit(
+    app: AppHandle,
+    per_minute: f64,
+    burst: i64,
+) -> Result<NotificationRateLimit, String> {
+    let limit = NotificationRateLimit { per_minute, burst };
+    validate_notification_rate_limit(&limit)?;
+
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    let raw = serde_json::to_string(&limit)
+        .map_err(|e| format!("failed to encode notification rate limit: {e}"))?;
+    set_setting(&conn, NOTIFICATION_RATE_LIMIT_SETTING_KEY, &raw)?;
+    apply_notification_rate_limit(&app, limit);
+    Ok(limit)
+}
+
+const PAUSE_ON_BATTERY_SETTING_KEY: &str = "pause_on_battery";
+
+fn load_pause_on_battery(conn: &Connection) -> Result<bool, String> {
+    Ok(get_setting(conn, PAUSE_ON_BATTERY_SETTING_KEY)?.is_some_and(|raw| raw == "true"))
+}
+
+const CONFIRMATION_TIMEOUT_SETTING_KEY: &str = "confirmation_timeout_ms";
+const DEFAULT_CONFIRMATION_TIMEOUT_MS: i64 = 15 * 60 * 1000;
+
+fn default_confirmation_timeout_ms() -> i64 {
+    DEFAULT_CONFIRMATION_TIMEOUT_MS
+}
+
+fn load_confirmation_timeout_ms(conn: &Connection) -> Result<i64, String> {
+    Ok(get_setting(conn, CONFIRMATION_TIMEOUT_SETTING_KEY)?
+        .and_then(|raw| raw.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_CONFIRMATION_TIMEOUT_MS))
+}
 
-    match status.as_str() {
-        "success" => {
-            let _ = app.emit("task_completed", task.id.clone());
-        }
-        _ => {
-            let _ = app.emit(
-                "task_failed",
-                serde_json::json!({
-                    "id": task.id,
-                    "error": error.unwrap_or_else(|| "unknown error".to_string())
-                }),
-            );
-        }
-    }
+const MAX_RESULT_BYTES_SETTING_KEY: &str = "max_result_bytes";
+const DEFAULT_MAX_RESULT_BYTES: usize = 8 * 1024;
 
-    Ok(())
+fn default_max_result_bytes() -> usize {
+    DEFAULT_MAX_RESULT_BYTES
 }
 
-// ====== Tauri commands ======
+fn load_max_result_bytes(conn: &Connection) -> Result<usize, String> {
+    Ok(get_setting(conn, MAX_RESULT_BYTES_SETTING_KEY)?
+        .and_then(|raw| raw.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_RESULT_BYTES))
+}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct ApiTrigger {
-    #[serde(rename = "type")]
-    pub r#type: String,
-    pub config: String,
+const MAX_ENABLED_TASKS_SETTING_KEY: &str = "max_enabled_tasks";
+
+/// 0 表示不限制
+fn load_max_enabled_tasks(conn: &Connection) -> Result<i64, String> {
+    Ok(get_setting(conn, MAX_ENABLED_TASKS_SETTING_KEY)?
+        .and_then(|raw| raw.parse::<i64>().ok())
+        .unwrap_or(0))
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct ApiAction {
-    #[serde(rename = "type")]
-    pub r#type: String,
-    pub config: String,
+/// 再启用一个任务之前检查是否已达 max_enabled_tasks 上限
+fn check_enabled_task_limit(conn: &Connection) -> Result<(), String> {
+    let max = load_max_enabled_tasks(conn)?;
+    if max <= 0 {
+        return Ok(());
+    }
+    let enabled: i64 = conn
+        .query_row("SELECT COUNT(*) FROM tasks WHERE enabled = 1", [], |r| {
+            r.get(0)
+        })
+        .map_err(|e| format!("failed to count enabled tasks: {e}"))?;
+    if enabled >= max {
+        return Err("enabled task limit reached".to_string());
+    }
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct ApiTask {
-    pub id: String,
-    pub name: String,
-    pub description: Option<String>,
-    pub trigger: ApiTrigger,
-    pub action: ApiAction,
-    pub enabled: bool,
-    pub last_run: Option<i64>,
-    pub next_run: Option<i64>,
-    pub metadata: Option<serde_json::Value>,
-    pub created_at: i64,
-    pub updated_at: Option<i64>,
+/// 限制同时启用的任务数（低配机器/统一管理的部署用），0 为不限制；已启用的任务不受影响
+#[tauri::command]
+pub fn scheduler_set_max_enabled_tasks(app: AppHandle, n: i64) -> Result<(), String> {
+    if n < 0 {
+        return Err("max_enabled_tasks must be >= 0".to_string());
+    }
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    if n == 0 {
+        delete_setting(&conn, MAX_ENABLED_TASKS_SETTING_KEY)
+    } else {
+        set_setting(&conn, MAX_ENABLED_TASKS_SETTING_KEY, &n.to_string())
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct ApiTaskExecution {
-    pub id: String,
-    pub task_id: String,
-    pub status: String,
-    pub started_at: i64,
-    pub completed_at: Option<i64>,
-    pub result: Option<String>,
-    pub error: Option<String>,
-    pub duration: Option<i64>,
+const DISABLED_ACTION_TYPES_SETTING_KEY: &str = "disabled_action_types";
+
+fn load_disabled_action_types(conn: &Connection) -> Result<Vec<String>, String> {
+    Ok(get_setting(conn, DISABLED_ACTION_TYPES_SETTING_KEY)?
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct IntervalTriggerConfig {
-    #[serde(rename = "type")]
-    _type: String,
-    seconds: i64,
+/// 去空白、去重、排序；允许还不存在的类型（如 shell/http），先禁用再上线
+fn normalize_action_types(types: Vec<String>) -> Result<Vec<String>, String> {
+    let mut out: Vec<String> = types.into_iter().map(|t| t.trim().to_string()).collect();
+    if out.iter().any(|t| t.is_empty()) {
+        return Err("action type must not be empty".to_string());
+    }
+    out.sort();
+    out.dedup();
+    Ok(out)
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct CronTriggerConfig {
-    #[serde(rename = "type")]
-    _type: String,
-    expression: String,
+fn save_disabled_action_types(conn: &Connection, types: &[String]) -> Result<(), String> {
+    if types.is_empty() {
+        return delete_setting(conn, DISABLED_ACTION_TYPES_SETTING_KEY);
+    }
+    let raw = serde_json::to_string(types)
+        .map_err(|e| format!("failed to encode disabled action types: {e}"))?;
+    set_setting(conn, DISABLED_ACTION_TYPES_SETTING_KEY, &raw)
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct NotificationActionConfig {
-    #[serde(rename = "type")]
-    _type: String,
-    title: String,
-    body: String,
-    #[serde(default)]
-    action_button: Option<String>,
-    #[serde(default)]
-    action_callback: Option<String>,
+#[tauri::command]
+pub fn scheduler_get_disabled_action_types(app: AppHandle) -> Result<Vec<String>, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    load_disabled_action_types(&conn)
 }
 
-#[derive(Debug, Deserialize)]
+/// 全局禁用某些 action 类型（家长控制/管理用），不管单个任务怎么配置；返回规范化后的列表
+#[tauri::command]
+pub fn scheduler_set_disabled_action_types(
+    app: AppHandle,
+    types: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let types = normalize_action_types(types)?;
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+    save_disabled_action_types(&conn, &types)?;
+    Ok(types)
+}
+
+// 未设置过日志级别时与 logging::init 的默认值一致
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// 调度器全局配置的汇总视图；各项仍按原来的 key 存在 scheduler_settings 里，
+/// 单独的 get/set 命令继续可用
+#[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
-struct AgentTaskActionConfig {
-    #[serde(rename = "type")]
-    _type: String,
-    prompt: String,
+pub struct SchedulerConfig {
+    pub dnd: DndState,
+    pub rate_limits: RateLimits,
+    pub log_level: String,
+    /// 数据库里 result 列保留的最大字节数，超出部分落到文件
+    #[serde(default = "default_max_result_bytes")]
+    pub max_result_bytes: usize,
+    /// 电池供电时暂停未标记 runOnBattery 的任务
     #[serde(default)]
-    tools_allowed: Option<Vec<String>>,
+    pub pause_on_battery: bool,
+    /// 待确认的执行多久没人处理就过期
+    #[serde(default = "default_confirmation_timeout_ms")]
+    pub confirmation_timeout_ms: i64,
+    /// 全局禁用的 action 类型，命中的任务到点只记一条 blocked
     #[serde(default)]
-    max_steps: Option<i64>,
+    pub disabled_action_types: Vec<String>,
+    #[serde(default)]
+    pub notification_rate_limit: NotificationRateLimit,
+    /// 同时启用的任务数上限，0 为不限制
+    #[serde(default)]
+    pub max_enabled_tasks: i64,
+    /// 后台轮询间隔（毫秒），范围 [50, 60000]
+    #[serde(default = "default_tick_interval_ms")]
+    pub tick_interval_ms: u64,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct WorkflowActionConfig {
-    #[serde(rename = "type")]
-    _type: String,
-    workflow_id: String,
-    #[serde(default)]
-    input: Option<serde_json::Value>,
+#[tauri::command]
+pub fn scheduler_get_config(app: AppHandle) -> Result<SchedulerConfig, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    Ok(SchedulerConfig {
+        dnd: load_dnd(&conn, now_ms())?,
+        rate_limits: load_rate_limits(&conn)?,
+        log_level: get_setting(&conn, LOG_LEVEL_SETTING_KEY)?
+            .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string()),
+        max_result_bytes: load_max_result_bytes(&conn)?,
+        pause_on_battery: load_pause_on_battery(&conn)?,
+        confirmation_timeout_ms: load_confirmation_timeout_ms(&conn)?,
+        disabled_action_types: load_disabled_action_types(&conn)?,
+        notification_rate_limit: load_notification_rate_limit(&app, &conn)?,
+        max_enabled_tasks: load_max_enabled_tasks(&conn)?,
+        tick_interval_ms: load_tick_interval_ms(&conn)?,
+    })
 }
 
+/// 整体校验后在一个事务里写入全部配置；tick 每轮都会重新读取限流配置，
+/// 日志级别和 interval 下限在这里立即生效，无需重启调度器
 #[tauri::command]
-pub fn scheduler_create_task(
+pub fn scheduler_set_config(
     app: AppHandle,
-    name: String,
-    description: Option<String>,
-    trigger_type: String,
-    trigger_config: String,
-    action_type: String,
-    action_config: String,
-    enabled: bool,
-    metadata: Option<String>,
-) -> Result<String, String> {
-    let conn = open_db(&app)?;
+    cfg: SchedulerConfig,
+) -> Result<SchedulerConfig, String> {
+    validate_rate_limits(&cfg.rate_limits)?;
+    validate_notification_rate_limit(&cfg.notification_rate_limit)?;
+    if cfg.max_result_bytes < 256 {
+        return Err("max_result_bytes must be >= 256".to_string());
+    }
+    if cfg.confirmation_timeout_ms < 1_000 {
+        return Err("confirmation_timeout_ms must be >= 1000".to_string());
+    }
+    if cfg.max_enabled_tasks < 0 {
+        return Err("max_enabled_tasks must be >= 0".to_string());
+    }
+    if !(MIN_TICK_INTERVAL_MS..=MAX_TICK_INTERVAL_MS).contains(&cfg.tick_interval_ms) {
+        return Err(format!(
+            "tick_interval_ms must be between {MIN_TICK_INTERVAL_MS} and {MAX_TICK_INTERVAL_MS}"
+        ));
+    }
+    let level = crate::logging::parse_level(&cfg.log_level)?;
+    let disabled_action_types = normalize_action_types(cfg.disabled_action_types)?;
+    if cfg.dnd.enabled && cfg.dnd.until_ms.is_some_and(|until| until <= now_ms()) {
+        return Err("dnd until_ms must be in the future".to_string());
+    }
+
+    let mut conn = open_db(&app)?;
     ensure_tables(&conn)?;
 
-    let now = now_ms();
-    let id = Uuid::new_v4().to_string();
-    let next_run = if enabled {
-        compute_next_run(&trigger_type, &trigger_config, now)
-    } else {
-        None
+    let cfg = SchedulerConfig {
+        dnd: if cfg.dnd.enabled {
+            cfg.dnd
+        } else {
+            DndState {
+                enabled: false,
+                until_ms: None,
+            }
+        },
+        rate_limits: cfg.rate_limits,
+        log_level: level.to_string().to_lowercase(),
+        max_result_bytes: cfg.max_result_bytes,
+        pause_on_battery: cfg.pause_on_battery,
+        confirmation_timeout_ms: cfg.confirmation_timeout_ms,
+        disabled_action_types,
+        notification_rate_limit: cfg.notification_rate_limit,
+        max_enabled_tasks: cfg.max_enabled_tasks,
+        tick_interval_ms: cfg.tick_interval_ms,
     };
 
-    conn.execute(
-        r#"
-INSERT INTO tasks (
-  id, name, description,
-  trigger_type, trigger_config,
-  action_type, action_config,
-  enabled, last_run, next_run, metadata,
-  created_at, updated_at
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?, NULL, ?, ?, ?, NULL)
-"#,
-        params![
-            id,
-            name,
-            description,
-            trigger_type,
-            trigger_config,
-            action_type,
-            action_config,
-            if enabled { 1 } else { 0 },
-            next_run,
-            metadata,
-            now
-        ],
-    )
-    .map_err(|e| format!("failed to insert task: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("failed to begin transaction: {e}"))?;
+    if cfg.dnd.enabled {
+        let raw =
+            serde_json::to_string(&cfg.dnd).map_err(|e| format!("failed to encode dnd: {e}"))?;
+        set_setting(&tx, DND_SETTING_KEY, &raw)?;
+    } else {
+        delete_setting(&tx, DND_SETTING_KEY)?;
+    }
+    let raw = serde_json::to_string(&cfg.rate_limits)
+        .map_err(|e| format!("failed to encode rate limits: {e}"))?;
+    set_setting(&tx, RATE_LIMITS_SETTING_KEY, &raw)?;
+    set_setting(&tx, LOG_LEVEL_SETTING_KEY, &cfg.log_level)?;
+    set_setting(
+        &tx,
+        MAX_RESULT_BYTES_SETTING_KEY,
+        &cfg.max_result_bytes.to_string(),
+    )?;
+    set_setting(
+        &tx,
+        PAUSE_ON_BATTERY_SETTING_KEY,
+        &cfg.pause_on_battery.to_string(),
+    )?;
+    set_setting(
+        &tx,
+        CONFIRMATION_TIMEOUT_SETTING_KEY,
+        &cfg.confirmation_timeout_ms.to_string(),
+    )?;
+    save_disabled_action_types(&tx, &cfg.disabled_action_types)?;
+    let raw = serde_json::to_string(&cfg.notification_rate_limit)
+        .map_err(|e| format!("failed to encode notification rate limit: {e}"))?;
+    set_setting(&tx, NOTIFICATION_RATE_LIMIT_SETTING_KEY, &raw)?;
+    if cfg.max_enabled_tasks == 0 {
+        delete_setting(&tx, MAX_ENABLED_TASKS_SETTING_KEY)?;
+    } else {
+        set_setting(
+            &tx,
+            MAX_ENABLED_TASKS_SETTING_KEY,
+            &cfg.max_enabled_tasks.to_string(),
+        )?;
+    }
+    set_setting(
+        &tx,
+        TICK_INTERVAL_SETTING_KEY,
+        &cfg.tick_interval_ms.to_string(),
+    )?;
+    tx.commit()
+        .map_err(|e| format!("failed to commit transaction: {e}"))?;
 
-    Ok(id)
+    MIN_INTERVAL_SECONDS.store(cfg.rate_limits.min_interval_seconds, Ordering::Relaxed);
+    apply_notification_rate_limit(&app, cfg.notification_rate_limit);
+    apply_tick_interval_ms(cfg.tick_interval_ms);
+    log::set_max_level(level);
+    Ok(cfg)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeignKeyViolation {
+    pub table: String,
+    pub rowid: Option<i64>,
+    pub parent: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub ok: bool,
+    pub integrity_messages: Vec<String>,
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    pub db_size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VacuumReport {
+    pub size_before_bytes: Option<u64>,
+    pub size_after_bytes: Option<u64>,
 }
 
 #[tauri::command]
-pub fn scheduler_get_task(app: AppHandle, id: String) -> Result<ApiTask, String> {
+pub fn scheduler_check_integrity(app: AppHandle) -> Result<IntegrityReport, String> {
+    let path = db_path(&app)?;
     let conn = open_db(&app)?;
-    ensure_tables(&conn)?;
 
     let mut stmt = conn
-        .prepare(
-            r#"
-SELECT
-  id, name, description,
-  trigger_type, trigger_config,
-  action_type, action_config,
-  enabled, last_run, next_run, metadata,
-  created_at, updated_at
-FROM tasks
-WHERE id = ?
-"#,
-        )
-        .map_err(|e| format!("failed to prepare get task: {e}"))?;
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| format!("failed to prepare integrity_check: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| r.get::<_, String>(0))
+        .map_err(|e| format!("failed to run integrity_check: {e}"))?;
+    let mut integrity_messages = Vec::new();
+    for row in rows {
+        integrity_messages.push(row.map_err(|e| format!("integrity_check map error: {e}"))?);
+    }
 
-    let row = stmt
-        .query_row(params![id], |r| {
-            Ok(DbTaskRow {
-                id: r.get(0)?,
-                name: r.get(1)?,
-                description: r.get(2)?,
-                trigger_type: r.get(3)?,
-                trigger_config: r.get(4)?,
-                action_type: r.get(5)?,
-                action_config: r.get(6)?,
-                enabled: r.get::<_, i64>(7)? == 1,
-                last_run: r.get(8)?,
-                next_run: r.get(9)?,
-                metadata: r.get(10)?,
-                created_at: r.get(11)?,
-                updated_at: r.get(12)?,
+    let mut stmt = conn
+        .prepare("PRAGMA foreign_key_check")
+        .map_err(|e| format!("failed to prepare foreign_key_check: {e}"))?;
+    let rows = stmt
+        .query_map([], |r| {
+            Ok(ForeignKeyViolation {
+                table: r.get(0)?,
+                rowid: r.get(1)?,
+                parent: r.get(2)?,
             })
         })
-        .map_err(|e| format!("task not found: {e}"))?;
+        .map_err(|e| format!("failed to run foreign_key_check: {e}"))?;
+    let mut foreign_key_violations = Vec::new();
+    for row in rows {
+        foreign_key_violations.push(row.map_err(|e| format!("foreign_key_check map error: {e}"))?);
+    }
+
+    // integrity_check 在库健康时只返回一行 "ok"
+    let ok = integrity_messages.len() == 1
+        && integrity_messages[0] == "ok"
+        && foreign_key_violations.is_empty();
+
+    Ok(IntegrityReport {
+        ok,
+        integrity_messages,
+        foreign_key_violations,
+        db_size_bytes: file_size(&path),
+    })
+}
+
+#[tauri::command]
+pub fn scheduler_vacuum(app: AppHandle) -> Result<VacuumReport, String> {
+    let path = db_path(&app)?;
+    let size_before_bytes = file_size(&path);
 
-    Ok(row_to_api_task(row))
+    let conn = open_db(&app)?;
+    conn.execute_batch("VACUUM")
+        .map_err(|e| format!("failed to vacuum: {e}"))?;
+    drop(conn);
+
+    Ok(VacuumReport {
+        size_before_bytes,
+        size_after_bytes: file_size(&path),
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenTask {
+    pub task_id: String,
+    pub name: String,
+    pub trigger_type: String,
+    /// 重新计算后能否排期
+    pub schedulable: bool,
+    pub next_run: Option<i64>,
+    pub reason: Option<String>,
+    /// `fix = true` 时是否已把算出的 next_run 写回
+    pub fixed: bool,
 }
 
+/// 排查"任务启用了却从不触发"：找出 enabled 但 next_run 为空的定时任务，重新计算并说明原因
 #[tauri::command]
-pub fn scheduler_get_all_tasks(app: AppHandle) -> Result<Vec<ApiTask>, String> {
+pub fn scheduler_find_broken_tasks(
+    app: AppHandle,
+    fix: Option<bool>,
+) -> Result<Vec<BrokenTask>, String> {
     let conn = open_db(&app)?;
     ensure_tables(&conn)?;
 
+    let fix = fix.unwrap_or(false);
+    let now = now_ms();
+
     let mut stmt = conn
         .prepare(
             r#"
-SELECT
-  id, name, description,
-  trigger_type, trigger_config,
-  action_type, action_config,
-  enabled, last_run, next_run, metadata,
-  created_at, updated_at
+SELECT id, name, trigger_type, trigger_config
 FROM tasks
-ORDER BY created_at DESC
+WHERE enabled = 1 AND next_run IS NULL
+ORDER BY created_at ASC
 "#,
         )
-        .map_err(|e| format!("failed to prepare list tasks: {e}"))?;
+        .map_err(|e| format!("failed to prepare broken task query: {e}"))?;
 
     let rows = stmt
         .query_map([], |r| {
-            Ok(DbTaskRow {
-                id: r.get(0)?,
-                name: r.get(1)?,
-                description: r.get(2)?,
-                trigger_type: r.get(3)?,
-                trigger_config: r.get(4)?,
-                action_type: r.get(5)?,
-                action_config: r.get(6)?,
-                enabled: r.get::<_, i64>(7)? == 1,
-                last_run: r.get(8)?,
-                next_run: r.get(9)?,
-                metadata: r.get(10)?,
-                created_at: r.get(11)?,
-                updated_at: r.get(12)?,
-            })
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+                r.get::<_, String>(3)?,
+            ))
         })
-        .map_err(|e| format!("failed to query tasks: {e}"))?;
+        .map_err(|e| format!("failed to query broken tasks: {e}"))?;
 
     let mut out = Vec::new();
     for row in rows {
-        out.push(row_to_api_task(
-            row.map_err(|e| format!("task map error: {e}"))?,
-        ));
+        let (task_id, name, trigger_type, trigger_config) =
+            row.map_err(|e| format!("broken task map error: {e}"))?;
+        if !is_time_based_trigger(&trigger_type) {
+            continue;
+        }
+
+        let (next_run, reason) = match try_compute_next_run(&trigger_type, &trigger_config, now) {
+            Ok(Some(next)) => (Some(next), None),
+            Ok(None) => (None, Some("trigger has no upcoming fire time".to_string())),
+            Err(err) => (None, Some(err)),
+        };
+
+        let mut fixed = false;
+        if fix {
+            if let Some(next) = next_run {
+                conn.execute(
+                    "UPDATE tasks SET next_run = ?, updated_at = ? WHERE id = ? AND next_run IS NULL",
+                    params![next, now, task_id],
+                )
+                .map_err(|e| format!("failed to fix task next_run: {e}"))?;
+                fixed = true;
+            }
+        }
+
+        out.push(BrokenTask {
+            task_id,
+            name,
+            trigger_type,
+            schedulable: next_run.is_some(),
+            next_run,
+            reason,
+            fixed,
+        });
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadSummary {
+    pub task_count: i64,
+    /// 被外部改成 next_run 为空、这次重新排期的任务数
+    pub rescheduled: i64,
+}
+
+/// pet.db 被外部工具或同步服务改过之后调用：重读 settings 同步内存里的缓存，
+/// 修复 next_run 丢失的任务，并发 scheduler_reloaded 让界面刷新
+#[tauri::command]
+pub fn scheduler_reload(app: AppHandle) -> Result<ReloadSummary, String> {
+    let conn = open_db(&app)?;
+    ensure_tables(&conn)?;
+
+    load_rate_limits(&conn)?;
+    load_notification_rate_limit(&app, &conn)?;
+    if let Some(level) = get_setting(&conn, LOG_LEVEL_SETTING_KEY)? {
+        log::set_max_level(crate::logging::parse_level(&level)?);
+    }
+
+    let task_ids = {
+        let mut stmt = conn
+            .prepare("SELECT id FROM tasks")
+            .map_err(|e| format!("failed to prepare task ids: {e}"))?;
+        let rows = stmt
+            .query_map([], |r| r.get::<_, String>(0))
+            .map_err(|e| format!("failed to query task ids: {e}"))?;
+        rows.collect::<Result<BTreeSet<_>, _>>()
+            .map_err(|e| format!("task id map error: {e}"))?
+    };
+    // 外部删掉的任务不再保留失败汇总的节流记录
+    runtime_state(&app)
+        .failing_notified_at
+        .lock()
+        .unwrap_or_else(|p| p.into_inner())
+        .retain(|id, _| task_ids.contains(id));
+
+    let rescheduled = scheduler_find_broken_tasks(app.clone(), Some(true))?
+        .iter()
+        .filter(|t| t.fixed)
+        .count() as i64;
+
+    let summary = ReloadSummary {
+        task_count: task_ids.len() as i64,
+        rescheduled,
+    };
+    log::info!(
+        "[Scheduler] reloaded {} tasks ({} rescheduled)",
+        summary.task_count,
+        summary.rescheduled
+    );
+    let _ = app.emit("scheduler_reloaded", summary.clone());
+    Ok(summary)
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+    /// 未通过时给用户/客服的处理建议
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestCheck {
+    fn new(name: &str, result: Result<String, (String, &str)>) -> Self {
+        match result {
+            Ok(detail) => Self {
+                name: name.to_string(),
+                passed: true,
+                detail,
+                hint: None,
+            },
+            Err((detail, hint)) => Self {
+                name: name.to_string(),
+                passed: false,
+                detail,
+                hint: Some(hint.to_string()),
+            },
+        }
     }
-    Ok(out)
 }
 
+/// 一键诊断：数据库、后台循环、通知通道、暂停状态、无法排期的任务，逐项给出结果和处理建议
 #[tauri::command]
-pub fn scheduler_update_task(
+pub fn scheduler_self_test(
     app: AppHandle,
-    id: String,
-    name: Option<String>,
-    description: Option<String>,
-    trigger_type: Option<String>,
-    trigger_config: Option<String>,
-    action_type: Option<String>,
-    action_config: Option<String>,
-    enabled: Option<bool>,
-    metadata: Option<String>,
-) -> Result<(), String> {
-    let conn = open_db(&app)?;
-    ensure_tables(&conn)?;
+    runner: State<'_, SchedulerRunner>,
+) -> Result<SelfTestReport, String> {
+    let mut checks = Vec::new();
 
-    // 读取现有任务用于计算 next_run
-    let existing = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
+    let database = open_db(&app).and_then(|conn| {
+        ensure_tables(&conn)?;
+        conn.query_row("SELECT COUNT(*) FROM tasks", [], |r| r.get::<_, i64>(0))
+            .map_err(|e| format!("failed to count tasks: {e}"))
+    });
+    let db_ok = database.is_ok();
+    checks.push(SelfTestCheck::new(
+        "database",
+        database
+            .map(|count| format!("{count} tasks"))
+            .map_err(|e| (e, "run scheduler_check_integrity, or restore a backup")),
+    ));
 
-    let final_trigger_type = trigger_type
-        .clone()
-        .unwrap_or(existing.trigger_type.clone());
-    let final_trigger_config = trigger_config
-        .clone()
-        .unwrap_or(existing.trigger_config.clone());
-    let final_enabled = enabled.unwrap_or(existing.enabled);
+    let now = runner.clock.now_ms();
+    let last_loop = runner.last_loop_ms.load(Ordering::Relaxed);
+    // 出错退避最长 SCHEDULER_MAX_BACKOFF_MS，再留一倍余量
+    let loop_alive = last_loop > 0 && now - last_loop <= 2 * SCHEDULER_MAX_BACKOFF_MS as i64;
+    checks.push(SelfTestCheck::new(
+        "background_loop",
+        if loop_alive {
+            Ok(format!("last loop {}ms ago", now - last_loop))
+        } else if last_loop == 0 {
+            Err(("background loop has not run".to_string(), "restart the app"))
+        } else {
+            Err((
+                format!("last loop {}ms ago", now - last_loop),
+                "check scheduler.log for repeated tick errors, then restart the app",
+            ))
+        },
+    ));
 
-    let now = now_ms();
-    let next_run = if final_enabled {
-        compute_next_run(&final_trigger_type, &final_trigger_config, now)
+    // 通知都是发事件给主窗口里的前端去展示的
+    checks.push(SelfTestCheck::new(
+        "notifications",
+        if app.get_webview_window("main").is_some() {
+            Ok("main window is available to receive notification events".to_string())
+        } else {
+            Err((
+                "main window not found".to_string(),
+                "restart the app so the main window is recreated",
+            ))
+        },
+    ));
+
+    let paused = if runner.safe_mode.load(Ordering::SeqCst) {
+        Err((
+            "safe mode is active after an unclean shutdown".to_string(),
+            "review recent tasks, then call scheduler_exit_safe_mode",
+        ))
+    } else if ON_BATTERY.load(Ordering::Relaxed) && db_ok {
+        // 读配置失败也只算这一项不通过，不让整个自检报错
+        match open_db(&app).and_then(|conn| load_pause_on_battery(&conn)) {
+            Ok(true) => Err((
+                "non-critical tasks are paused while on battery".to_string(),
+                "plug in, or turn off pauseOnBattery in the scheduler config",
+            )),
+            Ok(false) => Ok("scheduler is running".to_string()),
+            Err(e) => Err((
+                format!("failed to read pauseOnBattery: {e}"),
+                "run scheduler_check_integrity, or restore a backup",
+            )),
+        }
     } else {
-        None
+        Ok("scheduler is running".to_string())
     };
+    checks.push(SelfTestCheck::new("not_paused", paused));
 
-    conn.execute(
-        r#"
-UPDATE tasks
-SET
-  name = COALESCE(?, name),
-  description = COALESCE(?, description),
-  trigger_type = COALESCE(?, trigger_type),
-  trigger_config = COALESCE(?, trigger_config),
-  action_type = COALESCE(?, action_type),
-  action_config = COALESCE(?, action_config),
-  enabled = COALESCE(?, enabled),
-  metadata = COALESCE(?, metadata),
-  next_run = ?,
-  updated_at = ?
-WHERE id = ?
-"#,
-        params![
-            name,
-            description,
-            trigger_type,
-            trigger_config,
-            action_type,
-            action_config,
-            enabled.map(|b| if b { 1 } else { 0 }),
-            metadata,
-            next_run,
-            now,
-            id
-        ],
-    )
-    .map_err(|e| format!("failed to update task: {e}"))?;
+    if db_ok {
+        let broken = scheduler_find_broken_tasks(app.clone(), Some(false))?;
+        let fixable = broken.iter().filter(|t| t.schedulable).count();
+        checks.push(SelfTestCheck::new(
+            "broken_tasks",
+            if fixable == 0 {
+                Ok(format!("{} unschedulable tasks", broken.len()))
+            } else {
+                Err((
+                    format!("{fixable} enabled tasks have no next_run but can be scheduled"),
+                    "run scheduler_find_broken_tasks with fix = true",
+                ))
+            },
+        ));
+    }
 
-    Ok(())
+    Ok(SelfTestReport {
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+    })
 }
 
+/// 未指定 timezone 的触发器实际使用的时区（IANA 名称）；系统时区无法识别时为 "UTC"，与调度逻辑一致
 #[tauri::command]
-pub fn scheduler_delete_task(app: AppHandle, id: String) -> Result<(), String> {
-    let conn = open_db(&app)?;
-    ensure_tables(&conn)?;
-    conn.execute("DELETE FROM tasks WHERE id = ?", params![id])
-        .map_err(|e| format!("failed to delete task: {e}"))?;
-    Ok(())
+pub fn scheduler_get_system_timezone() -> String {
+    system_timezone().name().to_string()
 }
 
+/// 判断某个时间点所在的那一分钟是否命中 cron 表达式（与 `cron_next_ms` 使用同一套 5→6 段转换）
 #[tauri::command]
-pub fn scheduler_enable_task(app: AppHandle, id: String, enabled: bool) -> Result<(), String> {
+pub fn scheduler_cron_matches(
+    expression: String,
+    timestamp_ms: i64,
+    timezone: Option<String>,
+) -> Result<bool, String> {
+    let schedule = parse_cron_schedule(&expression)?;
+    let tz = resolve_timezone(timezone.as_deref())?;
+    let dt = tz
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .ok_or_else(|| format!("invalid timestamp: {timestamp_ms}"))?;
+    // 表达式的秒字段固定为 0，按分钟取整后再比较
+    let minute_start = dt
+        .with_second(0)
+        .and_then(|d| d.with_nanosecond(0))
+        .ok_or_else(|| format!("invalid timestamp: {timestamp_ms}"))?;
+    Ok(schedule.includes(minute_start))
+}
+
+/// 用 SQLite 在线备份 API 复制 pet.db，调度器无需停机；返回备份文件大小
+#[tauri::command]
+pub fn scheduler_create_backup(app: AppHandle, path: String) -> Result<u64, String> {
+    let target = PathBuf::from(path.trim());
+    if target.as_os_str().is_empty() {
+        return Err("backup path is empty".to_string());
+    }
+    if target == db_path(&app)? {
+        return Err("backup path must differ from the live database".to_string());
+    }
+
     let conn = open_db(&app)?;
-    ensure_tables(&conn)?;
+    conn.backup(DatabaseName::Main, &target, None)
+        .map_err(|e| format!("failed to back up database: {e}"))?;
 
-    let existing = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
-    let now = now_ms();
-    let next_run = if enabled {
-        compute_next_run(&existing.trigger_type, &existing.trigger_config, now)
-    } else {
-        None
-    };
+    file_size(&target).ok_or_else(|| "backup file was not created".to_string())
+}
 
-    conn.execute(
-        r#"UPDATE tasks SET enabled = ?, next_run = ?, updated_at = ? WHERE id = ?"#,
-        params![if enabled { 1 } else { 0 }, next_run, now, id],
-    )
-    .map_err(|e| format!("failed to enable task: {e}"))?;
+/// 只读打开备份文件，确认是完整的 SQLite 库且包含调度器需要的表
+fn validate_backup_file(path: &Path) -> Result<(), String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("failed to open backup: {e}"))?;
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |r| r.get(0))
+        .map_err(|e| format!("backup is not a readable database: {e}"))?;
+    if integrity != "ok" {
+        return Err(format!("backup failed integrity check: {integrity}"));
+    }
 
+    for table in ["tasks", "task_executions"] {
+        let exists: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?",
+                params![table],
+                |r| r.get(0),
+            )
+            .map_err(|e| format!("failed to inspect backup schema: {e}"))?;
+        if exists == 0 {
+            return Err(format!("backup is missing table: {table}"));
+        }
+    }
     Ok(())
 }
 
+/// 从备份恢复：校验通过后暂停 tick，用在线备份 API 反向写回 pet.db，其它连接随后读到的就是新数据
 #[tauri::command]
-pub fn scheduler_execute_now(app: AppHandle, id: String) -> Result<(), String> {
-    let conn = open_db(&app)?;
+pub fn scheduler_restore_backup(
+    app: AppHandle,
+    runner: State<'_, SchedulerRunner>,
+    path: String,
+) -> Result<u64, String> {
+    let source = PathBuf::from(path.trim());
+    if !source.is_file() {
+        return Err(format!("backup file not found: {}", source.display()));
+    }
+    validate_backup_file(&source)?;
+
+    let _paused = runner.pause_ticks();
+    let mut conn = open_db(&app)?;
+    conn.restore(
+        DatabaseName::Main,
+        &source,
+        None::<fn(rusqlite::backup::Progress)>,
+    )
+    .map_err(|e| format!("failed to restore database: {e}"))?;
+    // 备份可能来自旧版本，补齐之后新增的表
     ensure_tables(&conn)?;
-    let task = get_db_task(&conn, &id)?.ok_or_else(|| "task not found".to_string())?;
-    execute_task(&app, &conn, &task)?;
-    Ok(())
+    drop(conn);
+
+    let _ = app.emit("scheduler_restored", ());
+    file_size(&db_path(&app)?).ok_or_else(|| "database file missing after restore".to_string())
 }
 
+/// 清空数据库重新开始：暂停 tick，先把当前 pet.db 在线备份为 app_data_dir 下带时间戳的归档，
+/// 再用一个只含调度器表的新库覆盖（与恢复备份同一方式，不需要关闭其它持有该文件的连接）。
+/// keep_settings 为 true 时保留 scheduler_settings。返回归档文件路径；
+/// 前端自己的表也会被清掉，收到 `scheduler_database_reset` 后需重新初始化 schema
 #[tauri::command]
-pub fn scheduler_get_executions(
+pub fn scheduler_reset_database(
     app: AppHandle,
-    task_id: String,
-    limit: Option<i64>,
-) -> Result<Vec<ApiTaskExecution>, String> {
-    let conn = open_db(&app)?;
-    ensure_tables(&conn)?;
-
-    let limit = limit.unwrap_or(50).clamp(1, 200);
+    runner: State<'_, SchedulerRunner>,
+    keep_settings: bool,
+) -> Result<String, String> {
+    let live_path = db_path(&app)?;
+    let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+    let archive_path = live_path.with_file_name(format!("pet-{stamp}.db"));
+    let fresh_path = live_path.with_file_name("pet.reset.db");
 
-    let mut stmt = conn
-        .prepare(
-            r#"
-SELECT id, task_id, status, started_at, completed_at, result, error, duration
-FROM task_executions
-WHERE task_id = ?
-ORDER BY started_at DESC
-LIMIT ?
-"#,
-        )
-        .map_err(|e| format!("failed to prepare list executions: {e}"))?;
+    let _paused = runner.pause_ticks();
+    let mut conn = open_db(&app)?;
+    conn.backup(DatabaseName::Main, &archive_path, None)
+        .map_err(|e| format!("failed to archive database: {e}"))?;
 
-    let rows = stmt
-        .query_map(params![task_id, limit], |r| {
-            Ok(ApiTaskExecution {
-                id: r.get(0)?,
-                task_id: r.get(1)?,
-                status: r.get(2)?,
-                started_at: r.get(3)?,
-                completed_at: r.get(4)?,
-                result: r.get(5)?,
-                error: r.get(6)?,
-                duration: r.get(7)?,
-            })
-        })
-        .map_err(|e| format!("failed to query executions: {e}"))?;
+    let settings: Vec<(String, String, i64)> = if keep_settings {
+        ensure_tables(&conn)?;
+        let mut stmt = conn
+            .prepare("SELECT key, value, updated_at FROM scheduler_settings")
+            .map_err(|e| format!("failed to prepare settings query: {e}"))?;
+        let rows = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))
+            .map_err(|e| format!("failed to query settings: {e}"))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("failed to map settings: {e}"))?
+    } else {
+        Vec::new()
+    };
 
-    let mut out = Vec::new();
-    for row in rows {
-        out.push(row.map_err(|e| format!("execution map error: {e}"))?);
+    let _ = std::fs::remove_file(&fresh_path);
+    {
+        let fresh = Connection::open(&fresh_path)
+            .map_err(|e| format!("failed to create fresh database: {e}"))?;
+        ensure_tables(&fresh)?;
+        for (key, value, updated_at) in &settings {
+            fresh
+                .execute(
+                    "INSERT INTO scheduler_settings (key, value, updated_at) VALUES (?, ?, ?)",
+                    params![key, value, updated_at],
+                )
+                .map_err(|e| format!("failed to copy setting {key}: {e}"))?;
+        }
     }
-    Ok(out)
+
+    let restored = conn
+        .restore(
+            DatabaseName::Main,
+            &fresh_path,
+            None::<fn(rusqlite::backup::Progress)>,
+        )
+        .map_err(|e| format!("failed to reset database: {e}"));
+    let _ = std::fs::remove_file(&fresh_path);
+    restored?;
+    drop(conn);
+
+    log::warn!(
+        "[Scheduler] database reset, previous data archived to {}",
+        archive_path.display()
+    );
+    let _ = app.emit("scheduler_database_reset", ());
+    Ok(archive_path.to_string_lossy().into_owned())
 }
 
 fn get_db_task(conn: &Connection, id: &str) -> Result<Option<DbTaskRow>, String> {
     conn.query_row(
-        r#"
-SELECT
-  id, name, description,
-  trigger_type, trigger_config,
-  action_type, action_config,
-  enabled, last_run, next_run, metadata,
-  created_at, updated_at
+        &format!(
+            r#"
+SELECT {TASK_COLUMNS}
 FROM tasks
 WHERE id = ?
-"#,
+"#
+        ),
         params![id],
-        |r| {
-            Ok(DbTaskRow {
-                id: r.get(0)?,
-                name: r.get(1)?,
-                description: r.get(2)?,
-                trigger_type: r.get(3)?,
-                trigger_config: r.get(4)?,
-                action_type: r.get(5)?,
-                action_config: r.get(6)?,
-                enabled: r.get::<_, i64>(7)? == 1,
-                last_run: r.get(8)?,
-                next_run: r.get(9)?,
-                metadata: r.get(10)?,
-                created_at: r.get(11)?,
-                updated_at: r.get(12)?,
-            })
-        },
+        task_from_row,
     )
     .optional()
     .map_err(|e| format!("failed to get task: {e}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-01T00:00:00Z，周一
+    const START_MS: i64 = 1_704_067_200_000;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        configure_connection(&conn).unwrap();
+        ensure_tables(&conn).unwrap();
+        conn
+    }
+
+    fn trigger_type_of(trigger_config: &str) -> String {
+        let config: serde_json::Value = serde_json::from_str(trigger_config).unwrap();
+        config["type"].as_str().unwrap().to_string()
+    }
+
+    /// trigger_type 取自 trigger_config 里的 type
+    fn insert_task(conn: &Connection, id: &str, trigger_config: &str, next_run: Option<i64>) {
+        let trigger_type = trigger_type_of(trigger_config);
+        conn.execute(
+            r#"
+INSERT INTO tasks (
+  id, name, trigger_type, trigger_config, action_type, action_config,
+  enabled, next_run, created_at
+) VALUES (?, ?, ?, ?, 'notification', ?, 1, ?, ?)
+"#,
+            params![
+                id,
+                id,
+                trigger_type,
+                trigger_config,
+                r#"{"type":"notification","title":"t","body":"b"}"#,
+                next_run,
+                START_MS
+            ],
+        )
+        .unwrap();
+    }
+
+    fn cron_fires(expr: &str, n: usize) -> Vec<i64> {
+        let schedule = parse_cron_schedule(expr).unwrap();
+        let mut out = Vec::new();
+        let mut at = START_MS;
+        while out.len() < n {
+            at = cron_next_ms(&schedule, Tz::UTC, at).unwrap();
+            out.push(at);
+        }
+        out
+    }
+
+    #[test]
+    fn cron_named_weekday_range_matches_numeric() {
+        let numeric = cron_fires("0 9 * * 1-5", 20);
+        assert_eq!(cron_fires("0 9 * * MON-FRI", 20), numeric);
+        assert_eq!(cron_fires("0 9 * * mon-fri", 20), numeric);
+        assert_eq!(cron_fires("0 9 * * Mon,Tue,Wed,Thu,Fri", 20), numeric);
+    }
+
+    #[test]
+    fn cron_step_matches_explicit_list() {
+        assert_eq!(
+            cron_fires("*/15 * * * *", 20),
+            cron_fires("0,15,30,45 * * * *", 20)
+        );
+    }
+
+    #[test]
+    fn cron_named_months_match_numeric() {
+        let numeric = cron_fires("0 9 1 1,7 *", 6);
+        assert_eq!(cron_fires("0 9 1 JAN,JUL *", 6), numeric);
+        assert_eq!(cron_fires("0 9 1 jan,jul *", 6), numeric);
+    }
+
+    #[test]
+    fn cron_sunday_as_seven_wraps() {
+        assert_eq!(
+            cron_fires("0 9 * * 5-7", 12),
+            cron_fires("0 9 * * FRI-SUN", 12)
+        );
+        assert_eq!(cron_fires("0 9 * * 7", 4), cron_fires("0 9 * * 0", 4));
+    }
+
+    #[test]
+    fn cron_normalizes_to_six_fields() {
+        assert_eq!(
+            normalize_cron_expression("30 9 * jan mon-fri").unwrap(),
+            "0 30 9 * JAN MON-FRI"
+        );
+        assert_eq!(
+            normalize_cron_expression("15 30 9 * * 1").unwrap(),
+            "15 30 9 * * MON"
+        );
+        assert!(normalize_cron_expression("0 9 * *").is_err());
+        assert!(normalize_cron_expression("0 9 * * FUNDAY").is_err());
+    }
+
+    fn new_york_daily(mode: &str) -> String {
+        format!(
+            r#"{{"type":"interval","seconds":86400,"intervalMode":"{mode}","timezone":"America/New_York"}}"#
+        )
+    }
+
+    fn new_york_local(ms: i64) -> NaiveDateTime {
+        Utc.timestamp_millis_opt(ms)
+            .unwrap()
+            .with_timezone(&Tz::America__New_York)
+            .naive_local()
+    }
+
+    #[test]
+    fn wallclock_interval_keeps_local_time_across_dst() {
+        // 2024-03-10 凌晨 2 点纽约进入夏令时，这一天只有 23 小时
+        let start = Tz::America__New_York
+            .with_ymd_and_hms(2024, 3, 9, 9, 0, 0)
+            .unwrap()
+            .timestamp_millis();
+        let mut at = start;
+        for day in 10..=12 {
+            at = try_compute_next_run("interval", &new_york_daily("wallclock"), at)
+                .unwrap()
+                .unwrap();
+            let local = new_york_local(at);
+            assert_eq!(local.day(), day);
+            assert_eq!((local.hour(), local.minute()), (9, 0));
+        }
+        let first = try_compute_next_run("interval", &new_york_daily("wallclock"), start)
+            .unwrap()
+            .unwrap();
+        assert_eq!(first - start, 23 * 3600 * 1000);
+    }
+
+    #[test]
+    fn elapsed_interval_drifts_across_dst() {
+        let start = Tz::America__New_York
+            .with_ymd_and_hms(2024, 3, 9, 9, 0, 0)
+            .unwrap()
+            .timestamp_millis();
+        let next = try_compute_next_run("interval", &new_york_daily("elapsed"), start)
+            .unwrap()
+            .unwrap();
+        assert_eq!(next - start, 86_400_000);
+        assert_eq!(new_york_local(next).hour(), 10);
+    }
+
+    const SKIP_WEEKENDS: &str = r#"{"skipRules":{"skipWeekends":true}}"#;
+
+    fn utc_time_of_day(ms: i64) -> (u32, u32) {
+        let dt = Utc.timestamp_millis_opt(ms).unwrap();
+        (dt.hour(), dt.minute())
+    }
+
+    fn utc_weekday(ms: i64) -> chrono::Weekday {
+        Utc.timestamp_millis_opt(ms).unwrap().weekday()
+    }
+
+    #[test]
+    fn skip_weekends_keeps_interval_time_of_day() {
+        let trigger = r#"{"type":"interval","seconds":86400,"timezone":"UTC"}"#;
+        // 周五 09:00 之后的下一次本应是周六 09:00，跳到周一 09:00
+        let friday = START_MS + 4 * 86_400_000 + 9 * 3_600_000;
+        let next = compute_next_run("interval", trigger, Some(SKIP_WEEKENDS), friday).unwrap();
+        assert_eq!(next, friday + 3 * 86_400_000);
+        assert_eq!(utc_time_of_day(next), (9, 0));
+
+        // 再往后几次仍是 09:00
+        let mut at = next;
+        for _ in 0..10 {
+            at = compute_next_run("interval", trigger, Some(SKIP_WEEKENDS), at).unwrap();
+            assert_eq!(utc_time_of_day(at), (9, 0));
+            assert!(!matches!(
+                utc_weekday(at),
+                chrono::Weekday::Sat | chrono::Weekday::Sun
+            ));
+        }
+    }
+
+    #[test]
+    fn skip_weekends_keeps_interval_phase() {
+        let trigger = r#"{"type":"interval","seconds":3600,"timezone":"UTC"}"#;
+        // 周五 23:30 起每小时一次，周末跳过后落在周一 00:30
+        let friday = START_MS + 4 * 86_400_000 + 23 * 3_600_000 + 30 * 60_000;
+        let next = compute_next_run("interval", trigger, Some(SKIP_WEEKENDS), friday).unwrap();
+        assert_eq!(utc_weekday(next), chrono::Weekday::Mon);
+        assert_eq!(utc_time_of_day(next), (0, 30));
+    }
+
+    #[test]
+    fn skip_rules_move_cron_to_first_slot_of_next_allowed_day() {
+        let trigger = r#"{"type":"cron","expression":"0 9,18 * * *","timezone":"UTC"}"#;
+        let friday_evening = START_MS + 4 * 86_400_000 + 18 * 3_600_000;
+        let next = compute_next_run("cron", trigger, Some(SKIP_WEEKENDS), friday_evening).unwrap();
+        assert_eq!(next, START_MS + 7 * 86_400_000 + 9 * 3_600_000);
+
+        let skip_monday = r#"{"skipRules":{"skipWeekends":true,"skipDates":["2024-01-08"]}}"#;
+        let next = compute_next_run("cron", trigger, Some(skip_monday), friday_evening).unwrap();
+        assert_eq!(next, START_MS + 8 * 86_400_000 + 9 * 3_600_000);
+    }
+
+    #[test]
+    fn skip_rules_that_exclude_everything_give_up() {
+        let trigger = r#"{"type":"cron","expression":"0 9 * * 6","timezone":"UTC"}"#;
+        assert_eq!(
+            compute_next_run("cron", trigger, Some(SKIP_WEEKENDS), START_MS),
+            None
+        );
+    }
+
+    #[test]
+    fn due_tasks_follow_mock_clock() {
+        let conn = test_db();
+        let trigger = r#"{"type":"cron","expression":"0 9 * * *","timezone":"UTC"}"#;
+        let first = compute_next_run("cron", trigger, None, START_MS).unwrap();
+        assert_eq!(first, START_MS + 9 * 3600 * 1000);
+        insert_task(&conn, "t1", trigger, Some(first));
+
+        let clock = MockClock::new();
+        clock.set(first - 1);
+        assert!(list_due_tasks(&conn, clock.now_ms()).unwrap().is_empty());
+
+        clock.set(first);
+        let due = list_due_tasks(&conn, clock.now_ms()).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, "t1");
+        assert_eq!(
+            compute_next_run("cron", trigger, None, clock.now_ms()),
+            Some(first + 86_400_000)
+        );
+    }
+
+    #[test]
+    fn missed_fires_counts_every_slot_up_to_cap() {
+        let conn = test_db();
+        let trigger = r#"{"type":"cron","expression":"*/5 * * * *","timezone":"UTC"}"#;
+        insert_task(&conn, "t1", trigger, Some(START_MS));
+        let task = get_db_task(&conn, "t1").unwrap().unwrap();
+
+        assert_eq!(missed_fires(&task, START_MS - 1), 0);
+        assert_eq!(missed_fires(&task, START_MS), 1);
+        // 00:00 到 01:00 每 5 分钟一次，含两端
+        assert_eq!(missed_fires(&task, START_MS + 3_600_000), 13);
+        assert_eq!(
+            missed_fires(&task, START_MS + 30 * 86_400_000),
+            CATCHUP_MAX_RUNS
+        );
+    }
+
+    #[test]
+    fn normalize_cron_node_rewrites_composite_children() {
+        let mut config = serde_json::json!({
+            "type": "composite",
+            "op": "and",
+            "children": [
+                { "type": "cron", "expression": "0 9 * * MON-FRI" },
+                { "type": "cron", "expression": "0 0 9 * * *" },
+                { "type": "event", "eventName": "wake" },
+            ],
+        });
+        assert!(normalize_cron_node(&mut config).unwrap());
+        assert_eq!(config["children"][0]["expression"], "0 0 9 * * MON-FRI");
+        assert_eq!(config["children"][1]["expression"], "0 0 9 * * *");
+        // 再跑一遍不再有改动
+        assert!(!normalize_cron_node(&mut config).unwrap());
+    }
+
+    fn next_run(trigger_config: &str, from_ms: i64) -> i64 {
+        try_compute_next_run(&trigger_type_of(trigger_config), trigger_config, from_ms)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn utc_ms(y: i32, m: u32, d: u32, h: u32, min: u32) -> i64 {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0)
+            .unwrap()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn weekly_trigger_counts_weeks_from_anchor() {
+        let trigger = r#"{"type":"weekly","everyNWeeks":2,"weekday":1,"time":"09:30","anchorDate":"2024-01-01","timezone":"UTC"}"#;
+        let first = next_run(trigger, START_MS);
+        assert_eq!(first, utc_ms(2024, 1, 1, 9, 30));
+        assert_eq!(next_run(trigger, first), utc_ms(2024, 1, 15, 9, 30));
+    }
+
+    #[test]
+    fn lunar_trigger_follows_chinese_calendar() {
+        // 2024 年春节（正月初一）是 2 月 10 日，2025 年是 1 月 29 日
+        let new_year = r#"{"type":"lunar","lunarMonth":1,"lunarDay":1,"time":"08:00","timezone":"Asia/Shanghai"}"#;
+        let first = next_run(new_year, START_MS);
+        assert_eq!(first, utc_ms(2024, 2, 10, 0, 0));
+        assert_eq!(next_run(new_year, first), utc_ms(2025, 1, 29, 0, 0));
+
+        // 不指定月份时每个农历月都触发：2024-01-25 是腊月十五
+        let every_fifteenth =
+            r#"{"type":"lunar","lunarDay":15,"time":"08:00","timezone":"Asia/Shanghai"}"#;
+        assert_eq!(
+            next_run(every_fifteenth, START_MS),
+            utc_ms(2024, 1, 25, 0, 0)
+        );
+    }
+
+    #[test]
+    fn lunar_trigger_skips_leap_month_unless_asked() {
+        // 2023 年有闰二月，闰二月初一是 3 月 22 日；下一个正二月初一是 2024-03-10
+        let from = utc_ms(2023, 3, 1, 0, 0);
+        let second_month = |leap: bool| {
+            format!(
+                r#"{{"type":"lunar","lunarMonth":2,"lunarDay":1,"time":"08:00","matchLeapMonth":{leap},"timezone":"Asia/Shanghai"}}"#
+            )
+        };
+        assert_eq!(
+            next_run(&second_month(false), from),
+            utc_ms(2024, 3, 10, 0, 0)
+        );
+        assert_eq!(
+            next_run(&second_month(true), from),
+            utc_ms(2023, 3, 22, 0, 0)
+        );
+    }
+
+    #[test]
+    fn solar_trigger_applies_offset() {
+        let sunrise = |offset: i64| {
+            format!(
+                r#"{{"type":"solar","event":"sunrise","latitude":51.5,"longitude":0.0,"offsetMinutes":{offset},"timezone":"UTC"}}"#
+            )
+        };
+        let from = utc_ms(2024, 6, 21, 0, 0);
+        let at = next_run(&sunrise(0), from);
+        // 伦敦夏至日出约 03:43 UTC
+        assert!(
+            (utc_ms(2024, 6, 21, 3, 30)..utc_ms(2024, 6, 21, 4, 0)).contains(&at),
+            "{at}"
+        );
+        assert_eq!(next_run(&sunrise(-30), from), at - 30 * 60_000);
+    }
+
+    #[test]
+    fn composite_next_run_takes_earliest_for_or_latest_for_and() {
+        let composite = |op: &str| {
+            format!(
+                r#"{{"type":"composite","op":"{op}","children":[
+                    {{"type":"cron","expression":"0 9 * * *","timezone":"UTC"}},
+                    {{"type":"cron","expression":"0 18 * * *","timezone":"UTC"}}
+                ]}}"#
+            )
+        };
+        assert_eq!(
+            next_run(&composite("or"), START_MS),
+            utc_ms(2024, 1, 1, 9, 0)
+        );
+        assert_eq!(
+            next_run(&composite("and"), START_MS),
+            utc_ms(2024, 1, 1, 18, 0)
+        );
+    }
+
+    #[test]
+    fn composite_conditions_use_reported_idle_and_events() {
+        let state = SchedulerState::default();
+        let trigger = |op: &str| {
+            TriggerConfig::from_db(
+                "composite",
+                &format!(
+                    r#"{{"type":"composite","op":"{op}","children":[
+                        {{"type":"idle","idleSeconds":300}},
+                        {{"type":"event","eventName":"wake"}}
+                    ]}}"#
+                ),
+            )
+        };
+        let (and, or) = (trigger("and"), trigger("or"));
+        let since = START_MS;
+        assert!(!trigger_condition_met(&state, &and, since, START_MS));
+        assert!(!trigger_condition_met(&state, &or, since, START_MS));
+
+        state.record_event("wake".to_string(), START_MS + 10);
+        assert!(trigger_condition_met(&state, &or, since, START_MS + 10));
+        assert!(!trigger_condition_met(&state, &and, since, START_MS + 10));
+        // 上次执行之前的事件不算
+        assert!(!trigger_condition_met(
+            &state,
+            &or,
+            START_MS + 20,
+            START_MS + 20
+        ));
+
+        // 上报 200 秒空闲，100 秒后累计到 300 秒
+        state.record_idle(200, START_MS + 1_000);
+        assert!(!trigger_condition_met(
+            &state,
+            &and,
+            since,
+            START_MS + 100_000
+        ));
+        assert!(trigger_condition_met(
+            &state,
+            &and,
+            since,
+            START_MS + 101_000
+        ));
+        // 太久没有上报，视为已不空闲
+        let stale = START_MS + 1_000 + IDLE_REPORT_STALE_MS + 1;
+        assert_eq!(state.idle_seconds(stale), 0);
+    }
+
+    #[test]
+    fn notification_bucket_limits_burst_and_refills() {
+        let mut bucket = NotificationBucket::default();
+        // 未设置限额时不限制
+        assert!(bucket.take(START_MS));
+
+        let limit = NotificationRateLimit {
+            per_minute: 0.5,
+            burst: 2,
+        };
+        bucket.apply(limit, START_MS);
+        assert!(bucket.take(START_MS));
+        assert!(bucket.take(START_MS));
+        assert!(!bucket.take(START_MS));
+        // 每分钟补半个令牌
+        assert!(!bucket.take(START_MS + 60_000));
+        assert!(bucket.take(START_MS + 120_000));
+
+        // 空闲再久也最多攒 burst 个
+        let later = START_MS + 3_600_000;
+        assert!(bucket.take(later));
+        assert!(bucket.take(later));
+        assert!(!bucket.take(later));
+
+        // 限额没变时重新同步不会把桶装满
+        bucket.apply(limit, later);
+        assert!(!bucket.take(later));
+    }
+
+    #[test]
+    fn idle_tasks_fire_once_per_idle_stretch() {
+        let conn = test_db();
+        insert_task(&conn, "idle", r#"{"type":"idle","idleSeconds":300}"#, None);
+        let claim = |idle: i64| {
+            claim_idle_tasks(&conn, idle, START_MS)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(claim(100).is_empty());
+        assert_eq!(claim(300), vec!["idle"]);
+        // 同一段空闲里继续上报不再触发
+        assert!(claim(400).is_empty());
+        // 用户回来后下一段空闲可再次触发
+        assert!(claim(5).is_empty());
+        assert_eq!(claim(600), vec!["idle"]);
+    }
+
+    #[test]
+    fn in_flight_guard_tracks_overlapping_runs() {
+        let id = "in-flight-test";
+        assert!(!is_in_flight(id));
+        let first = InFlightGuard::enter(id);
+        let second = InFlightGuard::enter(id);
+        drop(first);
+        // 还有一次没结束，overlapPolicy 仍应视为重叠
+        assert!(is_in_flight(id));
+        drop(second);
+        assert!(!is_in_flight(id));
+    }
+
+    #[test]
+    fn execution_budget_ignores_runs_that_did_not_execute() {
+        let conn = test_db();
+        insert_task(
+            &conn,
+            "t1",
+            r#"{"type":"cron","expression":"0 9 * * *"}"#,
+            None,
+        );
+        for (i, status) in [
+            "success",
+            "failed",
+            "throttled",
+            "skipped",
+            "deferred",
+            "blocked",
+        ]
+        .iter()
+        .enumerate()
+        {
+            conn.execute(
+                "INSERT INTO task_executions (id, task_id, status, started_at) VALUES (?, 't1', ?, ?)",
+                params![format!("e{i}"), status, START_MS],
+            )
+            .unwrap();
+        }
+        assert_eq!(count_recent_executions(&conn, START_MS).unwrap(), 2);
+        assert_eq!(count_recent_executions(&conn, START_MS + 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn deleting_task_cascades_to_executions() {
+        let conn = test_db();
+        insert_task(
+            &conn,
+            "t1",
+            r#"{"type":"cron","expression":"0 9 * * *"}"#,
+            None,
+        );
+        conn.execute(
+            r#"
+INSERT INTO task_executions (id, task_id, status, started_at)
+VALUES ('e1', 't1', 'success', ?)
+"#,
+            params![START_MS],
+        )
+        .unwrap();
+
+        assert_eq!(delete_task(&conn, "t1").unwrap(), 1);
+        let remaining: i64 = conn
+            .query_row("SELECT COUNT(*) FROM task_executions", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
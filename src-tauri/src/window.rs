@@ -0,0 +1,426 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Monitor, PhysicalPosition, PhysicalSize, State, WebviewWindow};
+
+const PREFS_FILE_NAME: &str = "window-prefs.json";
+
+// 窗口相关的用户偏好：持久化在 app_data_dir 下，setup 时重新应用
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowPrefs {
+    pub visible_on_all_workspaces: bool,
+    /// 开发者模式：允许 release 构建里用 toggle_devtools 打开网页检查器
+    pub developer_mode: bool,
+    /// 显示系统标题栏（方便精确移动/缩放），开启时背景改为不透明
+    pub decorations: bool,
+    pub window_level: WindowLevel,
+}
+
+/// 宠物窗口所在的层级，macOS 上映射到 NSWindow 的 window level
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowLevel {
+    /// 在普通窗口之下、桌面壁纸之上（"壁纸伴侣"）
+    Desktop,
+    #[default]
+    Normal,
+    Floating,
+    /// 高于浮动面板和菜单栏
+    Top,
+}
+
+fn prefs_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let base_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("failed to resolve app_data_dir: {e}"))?;
+    std::fs::create_dir_all(&base_dir)
+        .map_err(|e| format!("failed to create dir {base_dir:?}: {e}"))?;
+    Ok(base_dir.join(PREFS_FILE_NAME))
+}
+
+pub fn load_prefs(app: &AppHandle) -> WindowPrefs {
+    // 文件缺失或损坏时退回默认值，不阻塞启动
+    prefs_path(app)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn save_prefs(app: &AppHandle, prefs: &WindowPrefs) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(prefs)
+        .map_err(|e| format!("failed to encode window prefs: {e}"))?;
+    std::fs::write(prefs_path(app)?, raw).map_err(|e| format!("failed to write window prefs: {e}"))
+}
+
+fn update_prefs(app: &AppHandle, f: impl FnOnce(&mut WindowPrefs)) -> Result<WindowPrefs, String> {
+    let mut prefs = load_prefs(app);
+    f(&mut prefs);
+    save_prefs(app, &prefs)?;
+    Ok(prefs)
+}
+
+fn main_window(app: &AppHandle) -> Result<WebviewWindow, String> {
+    app.get_webview_window("main")
+        .ok_or_else(|| "main window not found".to_string())
+}
+
+/// 启动时把持久化的偏好应用到主窗口
+pub fn apply_prefs(window: &WebviewWindow, prefs: &WindowPrefs) {
+    if prefs.decorations {
+        let _ = apply_decorations(window, true);
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if prefs.visible_on_all_workspaces {
+            let _ = window.set_visible_on_all_workspaces(true);
+        }
+        if prefs.window_level != WindowLevel::Normal {
+            let _ = apply_window_level(window, prefs.window_level);
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (window, prefs);
+    }
+}
+
+/// macOS 上对应 NSWindowCollectionBehaviorCanJoinAllSpaces，切换 Space 时宠物不会消失
+#[cfg(target_os = "macos")]
+pub fn apply_visible_on_all_workspaces(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    main_window(app)?
+        .set_visible_on_all_workspaces(enabled)
+        .map_err(|e| e.to_string())?;
+    update_prefs(app, |prefs| prefs.visible_on_all_workspaces = enabled)?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_visible_on_all_workspaces(app: AppHandle, enabled: bool) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        apply_visible_on_all_workspaces(&app, enabled)?;
+        if let Some(tray) = app.try_state::<crate::TrayState>() {
+            tray.all_workspaces_item
+                .set_checked(enabled)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (app, enabled);
+        Err("visible on all workspaces is only supported on macOS".to_string())
+    }
+}
+
+/// CGWindowLevel.h 里的取值；desktop 放在桌面图标层之上一层，宠物本身仍能点到
+#[cfg(target_os = "macos")]
+fn ns_window_level(level: WindowLevel) -> isize {
+    const CG_DESKTOP_ICON_WINDOW_LEVEL: isize = i32::MIN as isize + 41;
+    match level {
+        WindowLevel::Desktop => CG_DESKTOP_ICON_WINDOW_LEVEL + 1,
+        WindowLevel::Normal => 0,
+        WindowLevel::Floating => 3,
+        WindowLevel::Top => 25,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn apply_window_level(window: &WebviewWindow, level: WindowLevel) -> Result<(), String> {
+    // NSWindow 只能在主线程上改；指针本身不是 Send，按地址传过去
+    let ns_window = window.ns_window().map_err(|e| e.to_string())? as usize;
+    let ns_level = ns_window_level(level);
+    window
+        .run_on_main_thread(move || unsafe {
+            let ns_window = &*(ns_window as *const objc2::runtime::AnyObject);
+            let _: () = objc2::msg_send![ns_window, setLevel: ns_level];
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// 切换窗口层级并持久化；desktop 层级下若没开穿透，自动切到 regions 模式，
+/// 宠物以外的地方点击会落到桌面图标上
+#[tauri::command]
+pub fn set_window_level(app: AppHandle, level: WindowLevel) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        apply_window_level(&main_window(&app)?, level)?;
+        update_prefs(&app, |prefs| prefs.window_level = level)?;
+        let click_through_off = app.try_state::<ClickThroughState>().is_some_and(|state| {
+            *state.mode.lock().unwrap_or_else(|p| p.into_inner()) == ClickThroughMode::Off
+        });
+        if level == WindowLevel::Desktop && click_through_off {
+            apply_click_through_mode(&app, ClickThroughMode::Regions)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = (app, level);
+        Err("window level is only supported on macOS".to_string())
+    }
+}
+
+// 与 setup 中去掉标题栏后设置的透明背景一致
+const TRANSPARENT_BACKGROUND_JS: &str = "document.body.style.background = 'transparent'";
+const OPAQUE_BACKGROUND_JS: &str = "document.body.style.background = '#ffffff'";
+
+fn apply_decorations(window: &WebviewWindow, enabled: bool) -> Result<(), String> {
+    window.set_decorations(enabled).map_err(|e| e.to_string())?;
+    let js = if enabled {
+        OPAQUE_BACKGROUND_JS
+    } else {
+        TRANSPARENT_BACKGROUND_JS
+    };
+    window.eval(js).map_err(|e| e.to_string())
+}
+
+/// 切换标题栏/边框并持久化；开启时背景不透明，否则透明窗口上的边框看不清
+#[tauri::command]
+pub fn set_window_decorations(app: AppHandle, enabled: bool) -> Result<(), String> {
+    apply_decorations(&main_window(&app)?, enabled)?;
+    update_prefs(&app, |prefs| prefs.decorations = enabled)?;
+    Ok(())
+}
+
+/// 关闭开发者模式时顺带收起已经打开的检查器
+#[tauri::command]
+pub fn set_developer_mode(app: AppHandle, enabled: bool) -> Result<(), String> {
+    update_prefs(&app, |prefs| prefs.developer_mode = enabled)?;
+    if !enabled && !cfg!(debug_assertions) {
+        let window = main_window(&app)?;
+        if window.is_devtools_open() {
+            window.close_devtools();
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_developer_mode(app: AppHandle) -> bool {
+    load_prefs(&app).developer_mode
+}
+
+/// 打开/关闭主窗口的 devtools，返回切换后是否打开；release 构建需先开启开发者模式
+#[tauri::command]
+pub fn toggle_devtools(app: AppHandle) -> Result<bool, String> {
+    if !cfg!(debug_assertions) && !load_prefs(&app).developer_mode {
+        return Err("developer mode is disabled".to_string());
+    }
+    let window = main_window(&app)?;
+    if window.is_devtools_open() {
+        window.close_devtools();
+        Ok(false)
+    } else {
+        window.open_devtools();
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickThroughMode {
+    #[default]
+    Off,
+    Full,
+    /// 只在前端上报的不透明区域内接收鼠标，其余地方点到桌面
+    Regions,
+}
+
+/// 相对窗口内容区左上角的逻辑像素矩形
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+#[derive(Default)]
+pub struct ClickThroughState {
+    mode: Mutex<ClickThroughMode>,
+    regions: Mutex<Vec<Rect>>,
+}
+
+/// 切换穿透模式并立即作用到主窗口；托盘菜单也走这里，保证模式状态一致
+pub fn apply_click_through_mode(app: &AppHandle, mode: ClickThroughMode) -> Result<(), String> {
+    if let Some(state) = app.try_state::<ClickThroughState>() {
+        *state.mode.lock().unwrap_or_else(|p| p.into_inner()) = mode;
+    }
+    match mode {
+        ClickThroughMode::Off => main_window(app)?
+            .set_ignore_cursor_events(false)
+            .map_err(|e| e.to_string()),
+        ClickThroughMode::Full => main_window(app)?
+            .set_ignore_cursor_events(true)
+            .map_err(|e| e.to_string()),
+        ClickThroughMode::Regions => refresh_region_hover(app).map(|_| ()),
+    }
+}
+
+/// regions 模式下按当前光标位置决定是否穿透，返回光标是否落在可交互区域内
+fn refresh_region_hover(app: &AppHandle) -> Result<bool, String> {
+    let window = main_window(app)?;
+    let cursor = window.cursor_position().map_err(|e| e.to_string())?;
+    let origin = window.inner_position().map_err(|e| e.to_string())?;
+    let scale = window.scale_factor().map_err(|e| e.to_string())?;
+    let x = (cursor.x - f64::from(origin.x)) / scale;
+    let y = (cursor.y - f64::from(origin.y)) / scale;
+
+    let hovering = app.try_state::<ClickThroughState>().is_some_and(|state| {
+        state
+            .regions
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .iter()
+            .any(|rect| rect.contains(x, y))
+    });
+    window
+        .set_ignore_cursor_events(!hovering)
+        .map_err(|e| e.to_string())?;
+    Ok(hovering)
+}
+
+#[tauri::command]
+pub fn set_click_through_mode(app: AppHandle, mode: ClickThroughMode) -> Result<(), String> {
+    apply_click_through_mode(&app, mode)?;
+
+    #[cfg(target_os = "macos")]
+    if let Some(tray) = app.try_state::<crate::TrayState>() {
+        let full = mode == ClickThroughMode::Full;
+        tray.click_through_enabled
+            .store(full, std::sync::atomic::Ordering::Relaxed);
+        tray.click_through_item
+            .set_checked(full)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// 前端上报宠物不透明区域（窗口内逻辑像素）；regions 模式下立即重新判定一次
+#[tauri::command]
+pub fn set_click_through_regions(
+    app: AppHandle,
+    state: State<'_, ClickThroughState>,
+    rects: Vec<Rect>,
+) -> Result<(), String> {
+    *state.regions.lock().unwrap_or_else(|p| p.into_inner()) = rects;
+    if *state.mode.lock().unwrap_or_else(|p| p.into_inner()) == ClickThroughMode::Regions {
+        refresh_region_hover(&app)?;
+    }
+    Ok(())
+}
+
+/// 穿透时 webview 收不到鼠标事件，前端需轮询这个命令来跟随光标开关穿透；非 regions 模式下不做任何事
+#[tauri::command]
+pub fn update_click_through_hover(
+    app: AppHandle,
+    state: State<'_, ClickThroughState>,
+) -> Result<bool, String> {
+    let mode = *state.mode.lock().unwrap_or_else(|p| p.into_inner());
+    match mode {
+        ClickThroughMode::Regions => refresh_region_hover(&app),
+        ClickThroughMode::Off => Ok(true),
+        ClickThroughMode::Full => Ok(false),
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub position: PhysicalPosition<i32>,
+    pub size: PhysicalSize<u32>,
+    pub scale_factor: f64,
+    pub is_primary: bool,
+}
+
+fn same_monitor(a: &Monitor, b: &Monitor) -> bool {
+    a.name() == b.name() && a.position().x == b.position().x && a.position().y == b.position().y
+}
+
+/// index 与 window_move_to_monitor 的参数一致
+#[tauri::command]
+pub fn window_list_monitors(app: AppHandle) -> Result<Vec<MonitorInfo>, String> {
+    let monitors = app.available_monitors().map_err(|e| e.to_string())?;
+    let primary = app.primary_monitor().map_err(|e| e.to_string())?;
+    Ok(monitors
+        .iter()
+        .enumerate()
+        .map(|(index, m)| MonitorInfo {
+            index,
+            name: m.name().cloned(),
+            position: *m.position(),
+            size: *m.size(),
+            scale_factor: m.scale_factor(),
+            is_primary: primary.as_ref().is_some_and(|p| same_monitor(p, m)),
+        })
+        .collect())
+}
+
+/// 把宠物移到指定显示器的工作区内：保持在原显示器上的相对位置，原显示器未知时居中
+#[tauri::command]
+pub fn window_move_to_monitor(app: AppHandle, index: usize) -> Result<(), String> {
+    let monitors = app.available_monitors().map_err(|e| e.to_string())?;
+    let target = monitors.get(index).ok_or_else(|| {
+        format!(
+            "monitor index {index} out of range ({} available)",
+            monitors.len()
+        )
+    })?;
+
+    let window = main_window(&app)?;
+    let win_pos = window.outer_position().map_err(|e| e.to_string())?;
+    let win_size = window.outer_size().map_err(|e| e.to_string())?;
+
+    // 相对位置按窗口可移动范围的比例计算，0.5 即居中
+    let (rx, ry) = match window.current_monitor().map_err(|e| e.to_string())? {
+        Some(current) => {
+            let area = current.work_area();
+            (
+                ratio(win_pos.x - area.position.x, area.size.width, win_size.width),
+                ratio(
+                    win_pos.y - area.position.y,
+                    area.size.height,
+                    win_size.height,
+                ),
+            )
+        }
+        None => (0.5, 0.5),
+    };
+
+    let area = target.work_area();
+    let x = area.position.x + offset(rx, area.size.width, win_size.width);
+    let y = area.position.y + offset(ry, area.size.height, win_size.height);
+    window
+        .set_position(PhysicalPosition::new(x, y))
+        .map_err(|e| e.to_string())
+}
+
+fn ratio(offset: i32, area: u32, window: u32) -> f64 {
+    let free = area.saturating_sub(window);
+    if free == 0 {
+        return 0.5;
+    }
+    (f64::from(offset) / f64::from(free)).clamp(0.0, 1.0)
+}
+
+fn offset(ratio: f64, area: u32, window: u32) -> i32 {
+    let free = area.saturating_sub(window);
+    (ratio * f64::from(free)).round() as i32
+}
@@ -272,17 +272,35 @@ function App() {
 
     const onAgentExecute = (...args: unknown[]) => {
       const payload = args[0] as
-        | { prompt?: string; toolsAllowed?: string[]; maxSteps?: number }
+        | {
+            prompt?: string;
+            toolsAllowed?: string[];
+            maxSteps?: number;
+            context?: unknown;
+            model?: string;
+            executionId?: string;
+          }
         | undefined;
       if (!payload) return;
+      // 回报真实结果和耗时（慢任务提醒依赖它）；旧版后端不带 executionId
+      const report = (status: 'success' | 'failed', result?: string, error?: string) => {
+        if (!payload.executionId) return;
+        scheduler.completeExecution(payload.executionId, status, result, error).catch((err) => {
+          console.warn('[Scheduler] Failed to report execution result:', err);
+        });
+      };
       void (async () => {
         const prompt = payload.prompt?.trim();
-        if (!prompt) return;
+        if (!prompt) {
+          report('failed', undefined, 'empty prompt');
+          return;
+        }
 
         const { config } = useConfigStore.getState();
         if (config.llm.provider !== 'ollama' && !config.llm.apiKey) {
           // toast.error('未配置 API Key，无法执行定时任务');
           usePetStore.getState().showBubble('未配置 API Key，无法执行定时任务', 5200);
+          report('failed', undefined, 'missing API key');
           return;
         }
         // toast.info('正在执行定时任务…', 3000);
@@ -293,7 +311,7 @@ function App() {
           const runtime = new AgentRuntime({
             llmConfig: {
               provider: config.llm.provider,
-              model: config.llm.model,
+              model: payload.model || config.llm.model,
               apiKey: config.llm.apiKey,
               baseUrl: config.llm.baseUrl,
               temperature: config.llm.temperature,
@@ -312,11 +330,13 @@ function App() {
           const result = await runtime.run([{ role: 'user', content: prompt }], enabledTools);
           usePetStore.getState().setEmotion('happy');
           usePetStore.getState().showBubble(result.content.slice(0, 120) || '任务已完成', 6500);
+          report('success', JSON.stringify({ content: result.content }));
           // toast.success('定时任务已完成');
         } catch (err) {
           usePetStore.getState().setEmotion('confused');
           // toast.error(err instanceof Error ? `定时任务失败：${err.message}` : '定时任务失败');
           usePetStore.getState().showBubble('定时任务执行失败', 5200);
+          report('failed', undefined, err instanceof Error ? err.message : String(err));
         }
       })();
     };
@@ -2,7 +2,7 @@
 
 import { useState, useEffect } from 'react';
 import { getSchedulerManager } from '../../services/scheduler';
-import type { Task } from '../../types/scheduler';
+import type { Task, TaskCompletedEvent, TaskFailedEvent } from '../../types/scheduler';
 import { Button } from '@/components/ui/button';
 import { confirmAction } from '@/lib/confirm';
 
@@ -34,14 +34,14 @@ export function SchedulerTestPanel() {
     });
 
     scheduler.on('completed', (...args: unknown[]) => {
-      const id = args[0] as string;
-      addLog(`Task completed: ${id}`);
+      const data = args[0] as TaskCompletedEvent;
+      addLog(`Task completed: ${data.taskId} (${data.durationMs}ms)`);
       loadTasks();
     });
 
     scheduler.on('failed', (...args: unknown[]) => {
-      const data = args[0] as { id: string; error: string };
-      addLog(`Task failed: ${data.id} - ${data.error}`);
+      const data = args[0] as TaskFailedEvent;
+      addLog(`Task failed: ${data.taskId} - ${data.error}`);
     });
 
     scheduler.on('notification', (...args: unknown[]) => {
@@ -7,6 +7,9 @@ import type {
   TaskExecution,
   CreateTaskInput,
   UpdateTaskInput,
+  TaskCompletedEvent,
+  TaskFailedEvent,
+  TaskFailingRepeatedlyEvent,
 } from '@/types/scheduler';
 
 /**
@@ -42,19 +45,25 @@ export class SchedulerManager {
     this.unlistenFns.push(unlistenStarted);
 
     // Listen for task completed events
-    const unlistenCompleted = await listen<string>('task_completed', (event) => {
+    const unlistenCompleted = await listen<TaskCompletedEvent>('task_completed', (event) => {
       this.emit('completed', event.payload);
     });
     this.unlistenFns.push(unlistenCompleted);
 
     // Listen for task failed events
-    const unlistenFailed = await listen<{ id: string; error: string }>(
-      'task_failed',
+    const unlistenFailed = await listen<TaskFailedEvent>('task_failed', (event) => {
+      this.emit('failed', event.payload);
+    });
+    this.unlistenFns.push(unlistenFailed);
+
+    // Listen for throttled repeated-failure events (replace task_failed once a task keeps failing)
+    const unlistenFailingRepeatedly = await listen<TaskFailingRepeatedlyEvent>(
+      'task_failing_repeatedly',
       (event) => {
-        this.emit('failed', event.payload);
+        this.emit('failing_repeatedly', event.payload);
       }
     );
-    this.unlistenFns.push(unlistenFailed);
+    this.unlistenFns.push(unlistenFailingRepeatedly);
 
     // Listen for notification actions
     const unlistenNotification = await listen<{
@@ -71,6 +80,9 @@ export class SchedulerManager {
       prompt: string;
       toolsAllowed?: string[];
       maxSteps?: number;
+      context?: unknown;
+      model?: string;
+      executionId?: string;
     }>('task_agent_execute', (event) => {
       this.emit('agent_execute', event.payload);
     });
@@ -80,6 +92,7 @@ export class SchedulerManager {
     const unlistenWorkflow = await listen<{
       workflowId: string;
       input?: Record<string, unknown>;
+      executionId?: string;
     }>('task_workflow_execute', (event) => {
       this.emit('workflow_execute', event.payload);
     });
@@ -109,12 +122,11 @@ export class SchedulerManager {
     return await invoke<string>('scheduler_create_task', {
       name: input.name,
       description: input.description,
-      triggerType: input.trigger.type,
-      triggerConfig: JSON.stringify(input.trigger.config),
-      actionType: input.action.type,
-      actionConfig: JSON.stringify(input.action.config),
+      trigger: input.trigger.config,
+      action: input.action.config,
       enabled: input.enabled,
       metadata: input.metadata ? JSON.stringify(input.metadata) : undefined,
+      priority: input.priority,
     });
   }
 
@@ -142,16 +154,11 @@ export class SchedulerManager {
       id,
       name: updates.name,
       description: updates.description,
-      triggerType: updates.trigger?.type,
-      triggerConfig: updates.trigger
-        ? JSON.stringify(updates.trigger.config)
-        : undefined,
-      actionType: updates.action?.type,
-      actionConfig: updates.action
-        ? JSON.stringify(updates.action.config)
-        : undefined,
+      trigger: updates.trigger?.config,
+      action: updates.action?.config,
       enabled: updates.enabled,
       metadata: updates.metadata ? JSON.stringify(updates.metadata) : undefined,
+      priority: updates.priority,
     });
   }
 
@@ -186,6 +193,23 @@ export class SchedulerManager {
     });
   }
 
+  /**
+   * Report the real outcome of an agent/workflow action once it has finished running
+   */
+  async completeExecution(
+    executionId: string,
+    status: 'success' | 'failed',
+    result?: string,
+    error?: string
+  ): Promise<void> {
+    await invoke('scheduler_complete_execution', {
+      execId: executionId,
+      status,
+      result,
+      error,
+    });
+  }
+
   /**
    * Register event handler
    */
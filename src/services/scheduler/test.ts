@@ -2,6 +2,7 @@
 // Run this in the browser console to test scheduler functionality
 
 import { getSchedulerManager } from '@/services/scheduler';
+import type { TaskCompletedEvent } from '@/types/scheduler';
 
 export async function testScheduler() {
   console.log('=== TaskScheduler Test Suite ===\n');
@@ -62,8 +63,8 @@ export async function testScheduler() {
     });
 
     scheduler.on('completed', (...args: unknown[]) => {
-      const id = args[0] as string;
-      console.log('✓ Task completed:', id);
+      const data = args[0] as TaskCompletedEvent;
+      console.log('✓ Task completed:', data.taskId, `${data.durationMs}ms`);
     });
 
     scheduler.on('notification', (...args: unknown[]) => {
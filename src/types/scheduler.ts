@@ -4,24 +4,73 @@
  * Task trigger configuration - defines when a task should run
  */
 export interface Trigger {
-  type: 'cron' | 'interval' | 'event' | 'manual';
+  type:
+    | 'cron'
+    | 'interval'
+    | 'weekly'
+    | 'lunar'
+    | 'solar'
+    | 'idle'
+    | 'event'
+    | 'manual'
+    | 'composite';
   config: TriggerConfig;
 }
 
 export type TriggerConfig =
   | CronTriggerConfig
   | IntervalTriggerConfig
+  | WeeklyTriggerConfig
+  | LunarTriggerConfig
+  | SolarTriggerConfig
+  | IdleTriggerConfig
   | EventTriggerConfig
-  | ManualTriggerConfig;
+  | ManualTriggerConfig
+  | CompositeTriggerConfig;
 
 export interface CronTriggerConfig {
   type: 'cron';
-  expression: string; // e.g., "0 9 * * *" = every day at 9am
+  expression: string; // e.g., "0 9 * * *" = every day at 9am; an optional leading seconds field is accepted ("0 0 9 * * *")
+  timezone?: string; // IANA name, e.g. "Asia/Shanghai"; defaults to system local time
 }
 
 export interface IntervalTriggerConfig {
   type: 'interval';
   seconds: number; // Run every N seconds
+  intervalMode?: 'elapsed' | 'wallclock'; // wallclock keeps local time-of-day across DST (default: elapsed)
+  timezone?: string; // IANA name for wallclock mode; defaults to system local time
+}
+
+export interface WeeklyTriggerConfig {
+  type: 'weekly';
+  everyNWeeks: number; // >= 1
+  weekday: number; // 0 = Sunday ... 6 = Saturday
+  time: string; // Local wall-clock time "HH:MM"
+  anchorDate: string; // "YYYY-MM-DD", the week counting starts from
+  timezone?: string; // IANA name; defaults to system local time
+}
+
+export interface LunarTriggerConfig {
+  type: 'lunar';
+  lunarMonth?: number; // 1-12; omit to fire every lunar month (e.g. 初一/十五)
+  lunarDay: number; // 1-30; months without day 30 are skipped
+  time: string; // Local wall-clock time "HH:MM"
+  matchLeapMonth?: boolean; // Also fire in leap months (default: skip)
+  timezone?: string; // IANA name; defaults to system local time
+}
+
+export interface SolarTriggerConfig {
+  type: 'solar';
+  event: 'sunrise' | 'sunset';
+  latitude: number; // -90 ~ 90
+  longitude: number; // -180 ~ 180, east positive
+  offsetMinutes?: number; // Negative = before the event
+  timezone?: string; // IANA name, decides which local day is used; defaults to system local time
+}
+
+export interface IdleTriggerConfig {
+  type: 'idle';
+  idleSeconds: number; // Fires once per idle period, reported via scheduler_report_idle
 }
 
 export interface EventTriggerConfig {
@@ -34,11 +83,24 @@ export interface ManualTriggerConfig {
   type: 'manual'; // Only triggered manually by user
 }
 
+export interface CompositeTriggerConfig {
+  type: 'composite';
+  op: 'and' | 'or'; // or: earliest child fires; and: latest scheduled child, then idle/event children must also hold
+  children: TriggerConfig[]; // Nested at most 3 levels; event conditions are reported via scheduler_report_event
+}
+
 /**
  * Task action configuration - defines what to do when triggered
  */
 export interface Action {
-  type: 'agent_task' | 'notification' | 'workflow' | 'script';
+  type:
+    | 'agent_task'
+    | 'notification'
+    | 'workflow'
+    | 'pet_reaction'
+    | 'script'
+    | 'sequence'
+    | 'task_control';
   config: ActionConfig;
 }
 
@@ -46,13 +108,18 @@ export type ActionConfig =
   | AgentTaskActionConfig
   | NotificationActionConfig
   | WorkflowActionConfig
-  | ScriptActionConfig;
+  | PetReactionActionConfig
+  | ScriptActionConfig
+  | SequenceActionConfig
+  | TaskControlActionConfig;
 
 export interface AgentTaskActionConfig {
   type: 'agent_task';
   prompt: string;
   toolsAllowed?: string[]; // Optional: restrict which tools agent can use
   maxSteps?: number; // Optional: limit agent steps
+  context?: unknown; // Optional: passed through verbatim (session id, variables, ...)
+  model?: string; // Optional: model override for this run
 }
 
 export interface NotificationActionConfig {
@@ -69,11 +136,35 @@ export interface WorkflowActionConfig {
   input?: Record<string, unknown>;
 }
 
+export interface PetReactionActionConfig {
+  type: 'pet_reaction';
+  animation: 'idle' | 'happy' | 'eat' | 'tap_head' | 'clean' | 'sleep' | 'work' | 'study';
+  sound?: string;
+  durationMs?: number;
+}
+
 export interface ScriptActionConfig {
   type: 'script';
   code: string; // JavaScript code to execute (future feature)
 }
 
+export interface TaskControlActionConfig {
+  type: 'task_control';
+  targetTaskId: string;
+  operation: 'enable' | 'disable' | 'run_now' | 'snooze'; // run_now is refused while the target is already running
+  arg?: number; // snooze: minutes to postpone the target's next run
+}
+
+export interface SequenceActionConfig {
+  type: 'sequence';
+  steps: SequenceStep[]; // Run in order; stops at the first failed step unless it sets continueOnError
+}
+
+export interface SequenceStep {
+  action: Exclude<ActionConfig, SequenceActionConfig>; // Nested sequences are not supported
+  continueOnError?: boolean; // Failure does not stop the chain or fail the execution
+}
+
 /**
  * Task definition
  */
@@ -86,7 +177,9 @@ export interface Task {
   enabled: boolean;
   lastRun?: number; // Unix timestamp
   nextRun?: number; // Unix timestamp
+  previewNextRun?: number; // Same as nextRun when enabled; for disabled tasks, when it would run if enabled now (not persisted)
   metadata?: Record<string, unknown>; // User-defined data
+  priority?: number; // Higher runs first when many tasks are due at once (default: 0)
   createdAt: number;
   updatedAt?: number;
 }
@@ -97,12 +190,63 @@ export interface Task {
 export interface TaskExecution {
   id: string;
   taskId: string;
-  status: 'running' | 'success' | 'failed' | 'cancelled';
+  status:
+    | 'running'
+    | 'success'
+    | 'failed'
+    | 'cancelled'
+    | 'suppressed'
+    | 'throttled'
+    | 'skipped'
+    | 'deferred'
+    | 'pending_confirmation'
+    | 'declined'
+    | 'expired'
+    | 'blocked';
   startedAt: number;
   completedAt?: number;
   result?: string; // JSON string of execution result
   error?: string;
   duration?: number; // Execution time in milliseconds
+  slow?: boolean; // Duration exceeded metadata.warnIfSlowerThanMs
+  resultPath?: string; // Full result file when `result` was truncated
+  dispatchMs?: number; // Time spent parsing config and emitting the action
+}
+
+/**
+ * Payload of the `task_completed` event.
+ * Before this shape was introduced the payload was the bare task id string.
+ */
+export interface TaskCompletedEvent {
+  taskId: string;
+  executionId: string;
+  status: 'success' | 'suppressed' | 'throttled'; // suppressed = ran during Do Not Disturb; throttled = over the global notification limit
+  durationMs: number;
+  result: unknown | null; // Parsed TaskExecution.result
+}
+
+/**
+ * Payload of the `task_failed` event
+ */
+export interface TaskFailedEvent {
+  /** @deprecated use taskId */
+  id: string;
+  taskId: string;
+  executionId: string;
+  durationMs: number;
+  error: string;
+}
+
+/**
+ * Payload of the `task_failing_repeatedly` event.
+ * Sent instead of `task_failed` once a task fails 3 times in a row, at most every 10 minutes per task;
+ * `task_failed` resumes after the task succeeds again.
+ */
+export interface TaskFailingRepeatedlyEvent {
+  taskId: string;
+  executionId: string;
+  failureCount: number;
+  lastError: string;
 }
 
 /**
@@ -130,7 +274,7 @@ export interface SchedulerState {
 /**
  * Create task input (without generated fields)
  */
-export type CreateTaskInput = Omit<Task, 'id' | 'lastRun' | 'nextRun' | 'createdAt' | 'updatedAt'>;
+export type CreateTaskInput = Omit<Task, 'id' | 'lastRun' | 'nextRun' | 'previewNextRun' | 'createdAt' | 'updatedAt'>;
 
 /**
  * Update task input (partial update)